@@ -1,22 +1,36 @@
 use chrono::{DateTime, Utc};
 use lalrpop_util::ParseError;
+#[cfg(feature = "serialize")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use url::Url;
 use uuid::Uuid;
-use version_compare::version::Version;
+use version_compare::version::Version as RefVersion;
 
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
 use std::error;
 use std::fmt;
 
 use crate::lexer::{LexerError, Tok};
 
-#[derive(Debug, PartialEq)]
+// Text fields borrow from the decoded file where they can. into_owned() copies them out, for
+// hotlists that need to outlive the buffer or cross threads.
+//
+// With the "serialize" feature, the tree can be cached or sent elsewhere with serde. UUIDs are
+// hyphenated strings (raw bytes in binary formats), URLs and versions are written as-is,
+// timestamps are RFC 3339, and legacy encodings go by their WHATWG name.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Hotlist<'a> {
     pub version: Version<'a>,
     pub options: Options<'a>,
     pub entries: Vec<EntryKind<'a>>,
 }
 
-#[derive(Debug, PartialEq)]
+pub type HotlistBuf = Hotlist<'static>;
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Options<'a> {
     pub encoding: Encoding<'a>,
 }
@@ -27,28 +41,139 @@ pub(crate) enum SingleOp<'a> {
     Encoding(Encoding<'a>),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum Encoding<'a> {
     Utf8(Version<'a>),
+    // Opera 5/6 hotlists have no Options line, and are stored in a Windows code page.
+    Legacy(
+        #[cfg_attr(feature = "serialize", serde(with = "encoding_name"))]
+        &'static encoding_rs::Encoding,
+    ),
 }
 
-#[derive(Debug, PartialEq)]
+// A hotlist or encoding version like "2.0". The text is kept as written so that it can be
+// written back out, but versions compare by value, so "3" == "3.0".
+#[derive(Clone)]
+pub struct Version<'a>(Cow<'a, str>);
+
+impl<'a> Version<'a> {
+    pub fn parse<T: Into<Cow<'a, str>>>(version: T) -> Option<Self> {
+        let version = version.into();
+        RefVersion::from(&version)?;
+        Some(Version(version))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_owned(self) -> Version<'static> {
+        Version(Cow::Owned(self.0.into_owned()))
+    }
+
+    fn as_ref_version(&self) -> RefVersion<'_> {
+        // Checked in parse().
+        RefVersion::from(&self.0).unwrap()
+    }
+}
+
+impl<'a, 'b> PartialEq<Version<'b>> for Version<'a> {
+    fn eq(&self, other: &Version<'b>) -> bool {
+        self.as_ref_version() == other.as_ref_version()
+    }
+}
+
+impl<'a, 'b> PartialOrd<Version<'b>> for Version<'a> {
+    fn partial_cmp(&self, other: &Version<'b>) -> Option<Ordering> {
+        self.as_ref_version().partial_cmp(&other.as_ref_version())
+    }
+}
+
+impl<'a> fmt::Display for Version<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<'a> Serialize for Version<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<'de, 'a> Deserialize<'de> for Version<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let err = de::Error::custom(format!("invalid version {}", s));
+        Version::parse(s).ok_or(err)
+    }
+}
+
+#[cfg(feature = "serialize")]
+mod encoding_name {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(
+        e: &&'static encoding_rs::Encoding,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(e.name())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<&'static encoding_rs::Encoding, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        encoding_rs::Encoding::for_label(s.as_bytes())
+            .ok_or_else(|| de::Error::custom(format!("unknown encoding {}", s)))
+    }
+}
+
+impl<'a> fmt::Debug for Version<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Version({:?})", self.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum EntryKind<'a> {
     Folder(Folder<'a>),
     Note(Note<'a>),
+    Bookmark(Bookmark<'a>),
+    Contact(Contact<'a>),
+    Separator(Separator<'a>),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Folder<'a> {
     pub id: u32,
     pub uuid: Uuid,
-    pub name: &'a str,
+    pub name: Cow<'a, str>,
     pub timestamp: DateTime<Utc>,
     pub trash: bool,
     pub expanded: bool,
+    // KEY=value fields that aren't otherwise recognized, in the order they appeared.
+    pub extra: Vec<(Cow<'a, str>, Cow<'a, str>)>,
     pub entries: Vec<EntryKind<'a>>,
 }
 
+// Each *Field enum has an Invalid variant for a value that didn't parse, so that the entry can
+// report it and be skipped rather than ending the parse.
 #[derive(Debug, PartialEq)]
 pub(crate) enum FolderField<'a> {
     Id(u32),
@@ -57,16 +182,20 @@ pub(crate) enum FolderField<'a> {
     Timestamp(DateTime<Utc>),
     Expanded(bool),
     TrashFolder(bool),
+    Extra(&'a str, &'a str),
+    Invalid(HotlistError<'a>),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Note<'a> {
     pub id: u32,
     pub uuid: Uuid,
-    pub contents: Option<&'a str>,
-    pub url: Option<Url>,
+    pub contents: Option<Cow<'a, str>>,
+    pub url: Option<HotlistUrl<'a>>,
     pub timestamp: DateTime<Utc>,
     pub active: bool,
+    pub extra: Vec<(Cow<'a, str>, Cow<'a, str>)>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -74,9 +203,369 @@ pub(crate) enum NoteField<'a> {
     Id(u32),
     Uuid(Uuid),
     Contents(&'a str),
-    Url(Url),
+    Url(Option<HotlistUrl<'a>>),
+    Timestamp(DateTime<Utc>),
+    Active(bool),
+    Extra(&'a str, &'a str),
+    Invalid(HotlistError<'a>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Bookmark<'a> {
+    pub id: u32,
+    pub uuid: Uuid,
+    pub name: Cow<'a, str>,
+    pub url: Option<HotlistUrl<'a>>,
+    pub description: Option<Cow<'a, str>>,
+    pub short_name: Option<Cow<'a, str>>,
+    pub timestamp: DateTime<Utc>,
+    pub visited: Option<DateTime<Utc>>,
+    pub icon_file: Option<Cow<'a, str>>,
+    pub active: bool,
+    pub extra: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum BookmarkField<'a> {
+    Id(u32),
+    Uuid(Uuid),
+    Name(&'a str),
+    Url(Option<HotlistUrl<'a>>),
+    Description(&'a str),
+    ShortName(&'a str),
     Timestamp(DateTime<Utc>),
+    Visited(DateTime<Utc>),
+    IconFile(&'a str),
     Active(bool),
+    Extra(&'a str, &'a str),
+    Invalid(HotlistError<'a>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Contact<'a> {
+    pub id: u32,
+    pub uuid: Uuid,
+    pub name: Cow<'a, str>,
+    pub url: Option<HotlistUrl<'a>>,
+    pub description: Option<Cow<'a, str>>,
+    pub short_name: Option<Cow<'a, str>>,
+    pub timestamp: DateTime<Utc>,
+    pub mail: Option<Cow<'a, str>>,
+    pub phone: Option<Cow<'a, str>>,
+    pub fax: Option<Cow<'a, str>>,
+    pub postal_address: Option<Cow<'a, str>>,
+    pub picture_url: Option<Cow<'a, str>>,
+    pub icon: Option<Cow<'a, str>>,
+    pub active: bool,
+    pub extra: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum ContactField<'a> {
+    Id(u32),
+    Uuid(Uuid),
+    Name(&'a str),
+    Url(Option<HotlistUrl<'a>>),
+    Description(&'a str),
+    ShortName(&'a str),
+    Timestamp(DateTime<Utc>),
+    Mail(&'a str),
+    Phone(&'a str),
+    Fax(&'a str),
+    PostalAddress(&'a str),
+    PictureUrl(&'a str),
+    Icon(&'a str),
+    Active(bool),
+    Extra(&'a str, &'a str),
+    Invalid(HotlistError<'a>),
+}
+
+// In lenient mode, a URL that doesn't parse is kept verbatim.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum HotlistUrl<'a> {
+    Valid(Url),
+    Invalid(Cow<'a, str>),
+}
+
+impl<'a> fmt::Display for HotlistUrl<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotlistUrl::Valid(u) => u.fmt(f),
+            HotlistUrl::Invalid(s) => s.fmt(f),
+        }
+    }
+}
+
+// Older hotlists write bare "#SEPARATOR" lines, so none of the fields are required.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Separator<'a> {
+    pub id: Option<u32>,
+    pub uuid: Option<Uuid>,
+    pub extra: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum SeparatorField<'a> {
+    Id(u32),
+    Uuid(Uuid),
+    Extra(&'a str, &'a str),
+    Invalid(HotlistError<'a>),
+}
+
+impl<'a> Hotlist<'a> {
+    pub fn into_owned(self) -> HotlistBuf {
+        Hotlist {
+            version: self.version.into_owned(),
+            options: self.options.into_owned(),
+            entries: owned_entries(self.entries),
+        }
+    }
+}
+
+impl<'a> Options<'a> {
+    pub fn into_owned(self) -> Options<'static> {
+        Options {
+            encoding: match self.encoding {
+                Encoding::Utf8(v) => Encoding::Utf8(v.into_owned()),
+                Encoding::Legacy(e) => Encoding::Legacy(e),
+            },
+        }
+    }
+}
+
+impl<'a> EntryKind<'a> {
+    // Only separators can lack these.
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            EntryKind::Folder(f) => Some(f.id),
+            EntryKind::Note(n) => Some(n.id),
+            EntryKind::Bookmark(b) => Some(b.id),
+            EntryKind::Contact(c) => Some(c.id),
+            EntryKind::Separator(s) => s.id,
+        }
+    }
+
+    pub fn uuid(&self) -> Option<Uuid> {
+        match self {
+            EntryKind::Folder(f) => Some(f.uuid),
+            EntryKind::Note(n) => Some(n.uuid),
+            EntryKind::Bookmark(b) => Some(b.uuid),
+            EntryKind::Contact(c) => Some(c.uuid),
+            EntryKind::Separator(s) => s.uuid,
+        }
+    }
+
+    // Separators have no timestamp.
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            EntryKind::Folder(f) => Some(f.timestamp),
+            EntryKind::Note(n) => Some(n.timestamp),
+            EntryKind::Bookmark(b) => Some(b.timestamp),
+            EntryKind::Contact(c) => Some(c.timestamp),
+            EntryKind::Separator(_) => None,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EntryKind::Folder(_) => "folder",
+            EntryKind::Note(_) => "note",
+            EntryKind::Bookmark(_) => "bookmark",
+            EntryKind::Contact(_) => "contact",
+            EntryKind::Separator(_) => "separator",
+        }
+    }
+
+    // Names, or the first line of a note.
+    pub fn label(&self) -> String {
+        let text = match self {
+            EntryKind::Folder(f) => &f.name,
+            EntryKind::Note(n) => n.contents.as_deref().unwrap_or(""),
+            EntryKind::Bookmark(b) => &b.name,
+            EntryKind::Contact(c) => &c.name,
+            EntryKind::Separator(_) => "",
+        };
+
+        decode_line_breaks(text)
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_string()
+    }
+
+    pub fn into_owned(self) -> EntryKind<'static> {
+        match self {
+            EntryKind::Folder(f) => EntryKind::Folder(f.into_owned()),
+            EntryKind::Note(n) => EntryKind::Note(n.into_owned()),
+            EntryKind::Bookmark(b) => EntryKind::Bookmark(b.into_owned()),
+            EntryKind::Contact(c) => EntryKind::Contact(c.into_owned()),
+            EntryKind::Separator(s) => EntryKind::Separator(s.into_owned()),
+        }
+    }
+}
+
+impl<'a> Folder<'a> {
+    pub fn into_owned(self) -> Folder<'static> {
+        Folder {
+            id: self.id,
+            uuid: self.uuid,
+            name: owned(self.name),
+            timestamp: self.timestamp,
+            trash: self.trash,
+            expanded: self.expanded,
+            extra: owned_extra(self.extra),
+            entries: owned_entries(self.entries),
+        }
+    }
+}
+
+impl<'a> Note<'a> {
+    pub fn into_owned(self) -> Note<'static> {
+        Note {
+            id: self.id,
+            uuid: self.uuid,
+            contents: self.contents.map(owned),
+            url: self.url.map(HotlistUrl::into_owned),
+            timestamp: self.timestamp,
+            active: self.active,
+            extra: owned_extra(self.extra),
+        }
+    }
+}
+
+impl<'a> Bookmark<'a> {
+    pub fn into_owned(self) -> Bookmark<'static> {
+        Bookmark {
+            id: self.id,
+            uuid: self.uuid,
+            name: owned(self.name),
+            url: self.url.map(HotlistUrl::into_owned),
+            description: self.description.map(owned),
+            short_name: self.short_name.map(owned),
+            timestamp: self.timestamp,
+            visited: self.visited,
+            icon_file: self.icon_file.map(owned),
+            active: self.active,
+            extra: owned_extra(self.extra),
+        }
+    }
+}
+
+impl<'a> Contact<'a> {
+    pub fn into_owned(self) -> Contact<'static> {
+        Contact {
+            id: self.id,
+            uuid: self.uuid,
+            name: owned(self.name),
+            url: self.url.map(HotlistUrl::into_owned),
+            description: self.description.map(owned),
+            short_name: self.short_name.map(owned),
+            timestamp: self.timestamp,
+            mail: self.mail.map(owned),
+            phone: self.phone.map(owned),
+            fax: self.fax.map(owned),
+            postal_address: self.postal_address.map(owned),
+            picture_url: self.picture_url.map(owned),
+            icon: self.icon.map(owned),
+            active: self.active,
+            extra: owned_extra(self.extra),
+        }
+    }
+}
+
+impl<'a> Separator<'a> {
+    pub fn into_owned(self) -> Separator<'static> {
+        Separator {
+            id: self.id,
+            uuid: self.uuid,
+            extra: owned_extra(self.extra),
+        }
+    }
+}
+
+impl<'a> HotlistUrl<'a> {
+    pub fn into_owned(self) -> HotlistUrl<'static> {
+        match self {
+            HotlistUrl::Valid(u) => HotlistUrl::Valid(u),
+            HotlistUrl::Invalid(s) => HotlistUrl::Invalid(owned(s)),
+        }
+    }
+}
+
+// Opera stores line breaks in note text and descriptions as "\x02\x02". The emitters read each
+// pair as a line break and drop a lone "\x02".
+pub fn decode_line_breaks(s: &str) -> Cow<'_, str> {
+    if !s.contains('\x02') {
+        return Cow::Borrowed(s);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut possible_newline = false;
+    for c in s.chars() {
+        match c {
+            '\x02' if possible_newline => {
+                out.push('\n');
+                possible_newline = false;
+            }
+            '\x02' => possible_newline = true,
+            _ => {
+                out.push(c);
+                possible_newline = false;
+            }
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+// Calls f with every entry in tree order, folders before their contents, and the folders the
+// entry is in from the top level down.
+pub fn walk<'h, 'a, F>(entries: &'h [EntryKind<'a>], mut f: F)
+where
+    F: FnMut(&[&'h Folder<'a>], &'h EntryKind<'a>),
+{
+    fn walk_in<'h, 'a, F>(
+        entries: &'h [EntryKind<'a>],
+        folders: &mut Vec<&'h Folder<'a>>,
+        f: &mut F,
+    ) where
+        F: FnMut(&[&'h Folder<'a>], &'h EntryKind<'a>),
+    {
+        for e in entries {
+            f(folders, e);
+            if let EntryKind::Folder(folder) = e {
+                folders.push(folder);
+                walk_in(&folder.entries, folders, f);
+                folders.pop();
+            }
+        }
+    }
+
+    walk_in(entries, &mut Vec::new(), &mut f);
+}
+
+// Folder names from the top level down, as "/Work/Old", or "/" for the top level.
+pub fn folder_path<S: Borrow<str>>(folder: &[S]) -> String {
+    format!("/{}", folder.join("/"))
+}
+
+fn owned(s: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(s.into_owned())
+}
+
+fn owned_extra(extra: Vec<(Cow<str>, Cow<str>)>) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+    extra
+        .into_iter()
+        .map(|(k, v)| (owned(k), owned(v)))
+        .collect()
+}
+
+fn owned_entries(entries: Vec<EntryKind>) -> Vec<EntryKind<'static>> {
+    entries.into_iter().map(EntryKind::into_owned).collect()
 }
 
 // We squirrel this away in LexerError's UserError variant, because LexerError is already
@@ -84,9 +573,11 @@ pub(crate) enum NoteField<'a> {
 #[derive(Debug, PartialEq, Eq)]
 pub enum HotlistError<'a> {
     RequiredFieldMissing(&'a str, SpanInfo),
-    U32OutOfRange(&'a str),
-    InvalidUuid(&'a str),
-    InvalidUrl(&'a str),
+    // The rest carry the bad value and its byte offsets in the text.
+    U32OutOfRange(&'a str, (usize, usize)),
+    InvalidUuid(&'a str, (usize, usize)),
+    InvalidUrl(&'a str, (usize, usize)),
+    TimestampOutOfRange(&'a str, (usize, usize)),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -101,15 +592,44 @@ impl<'a> fmt::Display for HotlistError<'a> {
             HotlistError::RequiredFieldMissing(fld, _) => {
                 write!(f, "required Note/Folder field {} missing", fld)
             }
-            HotlistError::U32OutOfRange(u) => write!(f, "integer {} does not fit into u32", u),
-            HotlistError::InvalidUuid(u) => write!(f, "{} is not a valid UUID", u),
-            HotlistError::InvalidUrl(u) => write!(f, "{} is not a valid URL", u),
+            HotlistError::U32OutOfRange(u, _) => {
+                write!(f, "integer {} does not fit into u32", u)
+            }
+            HotlistError::InvalidUuid(u, _) => write!(f, "{} is not a valid UUID", u),
+            HotlistError::InvalidUrl(u, _) => write!(f, "{} is not a valid URL", u),
+            HotlistError::TimestampOutOfRange(t, _) => {
+                write!(f, "timestamp {} is out of the representable range", t)
+            }
         }
     }
 }
 
 impl<'a> error::Error for HotlistError<'a> {}
 
+// What lenient mode did instead of failing on a HotlistError.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fixup {
+    KeptRawUrl,
+    DerivedUuid(Uuid),
+    DefaultTimestamp(DateTime<Utc>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HotlistWarning<'a> {
+    pub error: HotlistError<'a>,
+    pub fixup: Fixup,
+}
+
+impl<'a> fmt::Display for HotlistWarning<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.fixup {
+            Fixup::KeptRawUrl => write!(f, "{}; keeping it as-is", self.error),
+            Fixup::DerivedUuid(u) => write!(f, "{}; using UUID {}", self.error, u),
+            Fixup::DefaultTimestamp(t) => write!(f, "{}; using {}", self.error, t),
+        }
+    }
+}
+
 impl<'a> From<HotlistError<'a>> for ParseError<usize, Tok<'_>, LexerError<'a>> {
     fn from(error: HotlistError<'a>) -> Self {
         ParseError::User {
@@ -0,0 +1,429 @@
+// Semantic checks on a parsed hotlist, for problems the grammar can't express. Each rule has a
+// severity, which can be changed or turned off, and its own exit code, so that a script can tell
+// what failed.
+
+use crate::ast::{walk, EntryKind, Hotlist};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum Rule {
+    // Two entries anywhere in the tree with the same ID.
+    DuplicateId,
+    DuplicateUuid,
+    MultipleTrash,
+    // Two folders with the same name in the same folder.
+    DuplicateFolderName,
+    FutureTimestamp,
+    ZeroTimestamp,
+    // A note with no contents and no URL.
+    EmptyNote,
+    // More than one entry with ACTIVE=YES.
+    MultipleActive,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum Severity {
+    // The rule isn't checked.
+    Allow,
+    Warning,
+    Error,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 8] = [
+        Rule::DuplicateId,
+        Rule::DuplicateUuid,
+        Rule::MultipleTrash,
+        Rule::DuplicateFolderName,
+        Rule::FutureTimestamp,
+        Rule::ZeroTimestamp,
+        Rule::EmptyNote,
+        Rule::MultipleActive,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::DuplicateId => "duplicate-id",
+            Rule::DuplicateUuid => "duplicate-uuid",
+            Rule::MultipleTrash => "multiple-trash",
+            Rule::DuplicateFolderName => "duplicate-folder-name",
+            Rule::FutureTimestamp => "future-timestamp",
+            Rule::ZeroTimestamp => "zero-timestamp",
+            Rule::EmptyNote => "empty-note",
+            Rule::MultipleActive => "multiple-active",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|r| r.name() == name)
+    }
+
+    // Problems that break merging, diffing or Opera itself are errors; the rest are warnings.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::DuplicateId | Rule::DuplicateUuid | Rule::MultipleTrash => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+
+    // Clear of the exit codes hl2html already uses for read, parse and write errors.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Rule::DuplicateId => 10,
+            Rule::DuplicateUuid => 11,
+            Rule::MultipleTrash => 12,
+            Rule::DuplicateFolderName => 13,
+            Rule::FutureTimestamp => 14,
+            Rule::ZeroTimestamp => 15,
+            Rule::EmptyNote => 16,
+            Rule::MultipleActive => 17,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Severity {
+    pub fn from_name(name: &str) -> Option<Severity> {
+        match name {
+            "allow" => Some(Severity::Allow),
+            "warning" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Allow => "allow",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+// Severities that differ from the defaults.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Rules {
+    severities: HashMap<Rule, Severity>,
+}
+
+impl Rules {
+    pub fn set(&mut self, rule: Rule, severity: Severity) {
+        self.severities.insert(rule, severity);
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub id: Option<u32>,
+    pub uuid: Option<Uuid>,
+    // Starts with the entry, e.g. `note 3 "Foo"`.
+    pub message: String,
+}
+
+// Findings grouped by rule, in the order of Rule::ALL, and in tree order within a rule. now is
+// what counts as the future.
+pub fn check(hl: &Hotlist, rules: &Rules, now: DateTime<Utc>) -> Vec<Finding> {
+    let mut entries = Vec::new();
+    walk(&hl.entries, |_, e| entries.push(e));
+
+    let mut findings = Vec::new();
+    for &rule in Rule::ALL.iter() {
+        let severity = rules.severity(rule);
+        if severity == Severity::Allow {
+            continue;
+        }
+
+        let mut report = |e: &EntryKind, message: String| {
+            findings.push(Finding {
+                rule,
+                severity,
+                id: e.id(),
+                uuid: e.uuid(),
+                message: format!("{}: {}", describe(e), message),
+            });
+        };
+
+        match rule {
+            Rule::DuplicateId => {
+                let mut seen: HashMap<u32, &EntryKind> = HashMap::new();
+                for e in &entries {
+                    if let Some(id) = e.id() {
+                        match seen.get(&id) {
+                            Some(first) => {
+                                report(e, format!("ID is also used by {}", describe(first)))
+                            }
+                            None => {
+                                seen.insert(id, *e);
+                            }
+                        }
+                    }
+                }
+            }
+            Rule::DuplicateUuid => {
+                let mut seen: HashMap<Uuid, &EntryKind> = HashMap::new();
+                for e in &entries {
+                    if let Some(uuid) = e.uuid() {
+                        match seen.get(&uuid) {
+                            Some(first) => report(
+                                e,
+                                format!("UUID {} is also used by {}", uuid, describe(first)),
+                            ),
+                            None => {
+                                seen.insert(uuid, *e);
+                            }
+                        }
+                    }
+                }
+            }
+            Rule::MultipleTrash => {
+                let mut trash = entries
+                    .iter()
+                    .filter(|e| matches!(e, EntryKind::Folder(f) if f.trash));
+                if let Some(first) = trash.next() {
+                    for e in trash {
+                        report(
+                            e,
+                            format!("{} is already the trash folder", describe(first)),
+                        );
+                    }
+                }
+            }
+            Rule::DuplicateFolderName => {
+                let mut folders = vec![&hl.entries[..]];
+                folders.extend(entries.iter().filter_map(|e| match e {
+                    EntryKind::Folder(f) => Some(&f.entries[..]),
+                    _ => None,
+                }));
+
+                for siblings in folders {
+                    let mut names = HashSet::new();
+                    for e in siblings {
+                        if let EntryKind::Folder(f) = e {
+                            if !names.insert(&f.name) {
+                                report(e, "another folder next to it has the same name".into());
+                            }
+                        }
+                    }
+                }
+            }
+            Rule::FutureTimestamp => {
+                for e in &entries {
+                    match e.timestamp() {
+                        Some(t) if t > now => report(e, format!("created in the future, {}", t)),
+                        _ => {}
+                    }
+                }
+            }
+            Rule::ZeroTimestamp => {
+                for e in &entries {
+                    match e.timestamp() {
+                        Some(t) if t.timestamp() == 0 => report(e, "CREATED is 0".into()),
+                        _ => {}
+                    }
+                }
+            }
+            Rule::EmptyNote => {
+                for e in &entries {
+                    if let EntryKind::Note(n) = e {
+                        let empty = n.contents.as_deref().unwrap_or("").is_empty();
+                        if empty && n.url.is_none() {
+                            report(e, "no contents and no URL".into());
+                        }
+                    }
+                }
+            }
+            Rule::MultipleActive => {
+                let mut active = entries.iter().filter(|e| match e {
+                    EntryKind::Note(n) => n.active,
+                    EntryKind::Bookmark(b) => b.active,
+                    EntryKind::Contact(c) => c.active,
+                    _ => false,
+                });
+                if let Some(first) = active.next() {
+                    for e in active {
+                        report(e, format!("ACTIVE=YES is also set on {}", describe(first)));
+                    }
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+// The exit code of the first rule with an error, or 0 if there are only warnings.
+pub fn exit_code(findings: &[Finding]) -> i32 {
+    findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .map(|f| f.rule)
+        .min()
+        .map_or(0, Rule::exit_code)
+}
+
+// Kind, ID and label.
+fn describe(e: &EntryKind) -> String {
+    match e.id() {
+        Some(id) => format!("{} {} {:?}", e.kind(), id, e.label()),
+        None => format!("{} {:?}", e.kind(), e.label()),
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.severity, self.message, self.rule)
+    }
+}
+
+impl Finding {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "rule": self.rule.name(),
+            "severity": self.severity.to_string(),
+            "exit_code": self.rule.exit_code(),
+            "id": self.id,
+            "uuid": self.uuid.map(|u| u.to_hyphenated_ref().to_string()),
+            "message": self.message,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, exit_code, Finding, Rule, Rules, Severity};
+    use crate::test_support::{parse, HOTLIST};
+
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn now() -> DateTime<Utc> {
+        Utc.timestamp(1500000000, 0)
+    }
+
+    // Findings for HOTLIST with more entries at the top level.
+    fn check_with(extra: &str, rules: &Rules) -> Vec<Finding> {
+        let text = format!("{}\n{}", HOTLIST, extra);
+        check(&parse(&text), rules, now())
+    }
+
+    fn found(extra: &str) -> Vec<(Rule, Option<u32>)> {
+        check_with(extra, &Rules::default())
+            .iter()
+            .map(|f| (f.rule, f.id))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean() {
+        assert!(found("").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_id() {
+        let extra = "#NOTE\n\tID=4\n\tUNIQUEID=0000000000000000000000000000000B\n\
+            \tNAME=Again.\n\tCREATED=1000000000\n";
+        assert_eq!(found(extra), vec![(Rule::DuplicateId, Some(4))]);
+        assert_eq!(
+            check_with(extra, &Rules::default())[0].message,
+            "note 4 \"Again.\": ID is also used by note 4 \"Code review.\""
+        );
+    }
+
+    #[test]
+    fn test_duplicate_uuid() {
+        let extra = "#NOTE\n\tID=11\n\tUNIQUEID=00000000000000000000000000000004\n\
+            \tNAME=Again.\n\tCREATED=1000000000\n";
+        assert_eq!(found(extra), vec![(Rule::DuplicateUuid, Some(11))]);
+    }
+
+    #[test]
+    fn test_multiple_trash() {
+        let extra = "#FOLDER\n\tID=11\n\tNAME=Bin\n\tCREATED=1000000000\n\
+            \tTRASH FOLDER=YES\n\tUNIQUEID=0000000000000000000000000000000B\n\n-\n";
+        assert_eq!(found(extra), vec![(Rule::MultipleTrash, Some(11))]);
+        assert_eq!(
+            check_with(extra, &Rules::default())[0].to_string(),
+            "error: folder 11 \"Bin\": folder 9 \"Trash\" is already the trash folder \
+            [multiple-trash]"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_folder_name() {
+        let extra = "#FOLDER\n\tID=11\n\tNAME=Work\n\tCREATED=1000000000\n\
+            \tUNIQUEID=0000000000000000000000000000000B\n\n-\n";
+        assert_eq!(found(extra), vec![(Rule::DuplicateFolderName, Some(11))]);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let extra = "#NOTE\n\tID=11\n\tUNIQUEID=0000000000000000000000000000000B\n\
+            \tNAME=Later.\n\tCREATED=2000000000\n\n\
+            #NOTE\n\tID=12\n\tUNIQUEID=0000000000000000000000000000000C\n\
+            \tNAME=Unknown.\n\tCREATED=0\n";
+        assert_eq!(
+            found(extra),
+            vec![
+                (Rule::FutureTimestamp, Some(11)),
+                (Rule::ZeroTimestamp, Some(12)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_note() {
+        let extra = "#NOTE\n\tID=11\n\tUNIQUEID=0000000000000000000000000000000B\n\
+            \tCREATED=1000000000\n";
+        assert_eq!(found(extra), vec![(Rule::EmptyNote, Some(11))]);
+    }
+
+    #[test]
+    fn test_multiple_active() {
+        let extra = "#NOTE\n\tID=11\n\tUNIQUEID=0000000000000000000000000000000B\n\
+            \tNAME=One.\n\tCREATED=1000000000\n\tACTIVE=YES\n\n\
+            #NOTE\n\tID=12\n\tUNIQUEID=0000000000000000000000000000000C\n\
+            \tNAME=Two.\n\tCREATED=1000000000\n\tACTIVE=YES\n";
+        assert_eq!(found(extra), vec![(Rule::MultipleActive, Some(12))]);
+    }
+
+    // An empty note that reuses an ID.
+    #[test]
+    fn test_severities() {
+        let extra = "#NOTE\n\tID=4\n\tUNIQUEID=0000000000000000000000000000000B\n\
+            \tCREATED=1000000000\n";
+        assert_eq!(exit_code(&check_with(extra, &Rules::default())), 10);
+
+        let mut rules = Rules::default();
+        rules.set(Rule::DuplicateId, Severity::Allow);
+        let findings = check_with(extra, &rules);
+        assert!(findings.iter().all(|f| f.rule != Rule::DuplicateId));
+        assert_eq!(exit_code(&findings), 0);
+
+        rules.set(Rule::EmptyNote, Severity::Error);
+        assert_eq!(exit_code(&check_with(extra, &rules)), 16);
+
+        assert_eq!(Rule::from_name("empty-note"), Some(Rule::EmptyNote));
+    }
+}
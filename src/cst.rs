@@ -0,0 +1,531 @@
+// A lossless view of a hotlist, built from the lexer's tokens. Each token keeps the whitespace
+// and line endings in front of it, and fields stay in the order they were written, repeats and
+// all. Writing the tree back out gives the input byte for byte, so an edit only changes the
+// bytes it touches. The parser still builds the ast from the same tokens; this is for tools that
+// rewrite files in place.
+
+use crate::lexer::{LexerError, RecoveringLexer, Tok};
+
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token<'a> {
+    // Whitespace since the previous token.
+    pub trivia: &'a str,
+    pub text: Cow<'a, str>,
+    // Where the token was in the input. Edits don't update this.
+    pub span: (usize, usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EntryType {
+    Folder,
+    Note,
+    Bookmark,
+    Contact,
+    Separator,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field<'a> {
+    pub key: Token<'a>,
+    pub equal: Option<Token<'a>>,
+    pub value: Option<Token<'a>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Entry<'a> {
+    pub kind: EntryType,
+    pub header: Token<'a>,
+    // A token that can't start a field, like a stray "=" or a line the lexer couldn't read, is
+    // kept as a field with only a key so that nothing is lost. The parser reports those.
+    pub fields: Vec<Field<'a>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Folder<'a> {
+    pub entry: Entry<'a>,
+    pub children: Vec<Node<'a>>,
+    // None if the file ends before the folder does.
+    pub end: Option<Token<'a>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node<'a> {
+    Entry(Entry<'a>),
+    Folder(Folder<'a>),
+    // A "-" with no folder to end.
+    StrayEnd(Token<'a>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SyntaxTree<'a> {
+    // The "Opera Hotlist version" and Options lines, or whatever comes before the first entry.
+    pub prelude: Vec<Token<'a>>,
+    pub nodes: Vec<Node<'a>>,
+    // Whitespace after the last token.
+    pub trailing: &'a str,
+}
+
+impl<'a> Field<'a> {
+    pub fn key(&self) -> &str {
+        &self.key.text
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_ref().map(|v| &*v.text)
+    }
+
+    pub fn set_value<T: Into<Cow<'a, str>>>(&mut self, value: T) {
+        let value = value.into();
+        let end = self.key.span.1;
+
+        if self.equal.is_none() {
+            self.equal = Some(Token {
+                trivia: "",
+                text: Cow::Borrowed("="),
+                span: (end, end),
+            });
+        }
+
+        match &mut self.value {
+            Some(v) => v.text = value,
+            None => {
+                self.value = Some(Token {
+                    trivia: "",
+                    text: value,
+                    span: (end, end),
+                })
+            }
+        }
+    }
+}
+
+impl<'a> Entry<'a> {
+    // Like the parser, the last of a repeated field wins.
+    pub fn field(&self, key: &str) -> Option<&Field<'a>> {
+        self.fields.iter().rev().find(|f| f.key() == key)
+    }
+
+    pub fn field_mut(&mut self, key: &str) -> Option<&mut Field<'a>> {
+        self.fields.iter_mut().rev().find(|f| f.key() == key)
+    }
+
+    // Changes the field in place if it's there, otherwise adds it after the others, indented the
+    // same way as the entry's first field.
+    pub fn set_field<T: Into<Cow<'a, str>>>(&mut self, key: &'a str, value: T) {
+        if let Some(f) = self.field_mut(key) {
+            f.set_value(value);
+            return;
+        }
+
+        let trivia = self
+            .fields
+            .first()
+            .and_then(|f| line_start(f.key.trivia))
+            .unwrap_or("\n\t");
+        let end = self
+            .fields
+            .last()
+            .and_then(|f| f.value.as_ref().or(f.equal.as_ref()))
+            .map_or(self.header.span.1, |t| t.span.1);
+
+        let mut field = Field {
+            key: Token {
+                trivia,
+                text: Cow::Borrowed(key),
+                span: (end, end),
+            },
+            equal: None,
+            value: None,
+        };
+        field.set_value(value);
+        self.fields.push(field);
+    }
+
+    // Removes every copy of the field, along with the line break in front of each.
+    pub fn remove_field(&mut self, key: &str) {
+        self.fields.retain(|f| f.key() != key);
+    }
+}
+
+impl<'a> SyntaxTree<'a> {
+    pub fn parse(input: &'a str) -> Result<Self, LexerError<'a>> {
+        let tokens = RecoveringLexer::new(input).collect::<Result<Vec<_>, _>>()?;
+        let mut builder = Builder {
+            input,
+            tokens,
+            pos: 0,
+            last: 0,
+        };
+
+        let mut prelude = Vec::new();
+        while let Some(tok) = builder.peek() {
+            if header_type(tok).is_some() || tok == Tok::FolderEnd {
+                break;
+            }
+            prelude.push(builder.next());
+        }
+
+        let (nodes, _) = builder.nodes(false);
+
+        Ok(Self {
+            prelude,
+            nodes,
+            trailing: &input[builder.last..],
+        })
+    }
+
+    // Depth-first, in file order.
+    pub fn find_entry_mut<F>(&mut self, mut pred: F) -> Option<&mut Entry<'a>>
+    where
+        F: FnMut(&Entry<'a>) -> bool,
+    {
+        find_in(&mut self.nodes, &mut pred)
+    }
+}
+
+fn find_in<'n, 'a, F>(nodes: &'n mut [Node<'a>], pred: &mut F) -> Option<&'n mut Entry<'a>>
+where
+    F: FnMut(&Entry<'a>) -> bool,
+{
+    for node in nodes {
+        match node {
+            Node::Entry(e) => {
+                if pred(e) {
+                    return Some(e);
+                }
+            }
+            Node::Folder(f) => {
+                if pred(&f.entry) {
+                    return Some(&mut f.entry);
+                }
+                if let Some(e) = find_in(&mut f.children, pred) {
+                    return Some(e);
+                }
+            }
+            Node::StrayEnd(_) => {}
+        }
+    }
+
+    None
+}
+
+struct Builder<'a> {
+    input: &'a str,
+    tokens: Vec<(usize, Tok<'a>, usize)>,
+    pos: usize,
+    // End of the last token taken, i.e. the start of the next token's trivia.
+    last: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn peek(&self) -> Option<Tok<'a>> {
+        self.tokens.get(self.pos).map(|(_, tok, _)| *tok)
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        let (lo, _, hi) = self.tokens[self.pos];
+        let token = Token {
+            trivia: &self.input[self.last..lo],
+            text: Cow::Borrowed(&self.input[lo..hi]),
+            span: (lo, hi),
+        };
+
+        self.pos += 1;
+        self.last = hi;
+        token
+    }
+
+    // Returns the "-" that ended the folder, if we're in one.
+    fn nodes(&mut self, in_folder: bool) -> (Vec<Node<'a>>, Option<Token<'a>>) {
+        let mut nodes = Vec::new();
+
+        while let Some(tok) = self.peek() {
+            if tok == Tok::FolderEnd {
+                let end = self.next();
+                if in_folder {
+                    return (nodes, Some(end));
+                }
+                nodes.push(Node::StrayEnd(end));
+                continue;
+            }
+
+            // Only headers and "-" end an entry, so this is always a header.
+            let kind = header_type(tok).unwrap_or(EntryType::Separator);
+            let entry = self.entry(kind);
+
+            if kind == EntryType::Folder {
+                let (children, end) = self.nodes(true);
+                nodes.push(Node::Folder(Folder {
+                    entry,
+                    children,
+                    end,
+                }));
+            } else {
+                nodes.push(Node::Entry(entry));
+            }
+        }
+
+        (nodes, None)
+    }
+
+    fn entry(&mut self, kind: EntryType) -> Entry<'a> {
+        let header = self.next();
+        let mut fields = Vec::new();
+
+        while let Some(tok) = self.peek() {
+            if header_type(tok).is_some() || tok == Tok::FolderEnd {
+                break;
+            }
+
+            let key = self.next();
+            let mut equal = None;
+            let mut value = None;
+
+            if !matches!(tok, Tok::Equal | Tok::Invalid)
+                && !is_value(tok)
+                && self.peek() == Some(Tok::Equal)
+            {
+                equal = Some(self.next());
+                if matches!(self.peek(), Some(t) if is_value(t)) {
+                    value = Some(self.next());
+                }
+            }
+
+            fields.push(Field { key, equal, value });
+        }
+
+        Entry {
+            kind,
+            header,
+            fields,
+        }
+    }
+}
+
+// The last line ending in some trivia and the indentation after it.
+fn line_start(trivia: &str) -> Option<&str> {
+    let nl = trivia.rfind('\n')?;
+    let start = if trivia[..nl].ends_with('\r') {
+        nl - 1
+    } else {
+        nl
+    };
+    Some(&trivia[start..])
+}
+
+fn header_type(tok: Tok) -> Option<EntryType> {
+    match tok {
+        Tok::FolderHeader => Some(EntryType::Folder),
+        Tok::NoteHeader => Some(EntryType::Note),
+        Tok::UrlHeader => Some(EntryType::Bookmark),
+        Tok::ContactHeader => Some(EntryType::Contact),
+        Tok::SeparatorHeader => Some(EntryType::Separator),
+        _ => None,
+    }
+}
+
+fn is_value(tok: Tok) -> bool {
+    matches!(
+        tok,
+        Tok::Integer(_)
+            | Tok::Uuid(_)
+            | Tok::Version(_)
+            | Tok::Yes
+            | Tok::No
+            | Tok::UrlBody(_)
+            | Tok::NoteBody(_)
+    )
+}
+
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.trivia, self.text)
+    }
+}
+
+impl<'a> fmt::Display for Field<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.key.fmt(f)?;
+        if let Some(eq) = &self.equal {
+            eq.fmt(f)?;
+        }
+        if let Some(v) = &self.value {
+            v.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for Entry<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.header.fmt(f)?;
+        for fld in &self.fields {
+            fld.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for Node<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Entry(e) => e.fmt(f),
+            Node::Folder(fld) => {
+                fld.entry.fmt(f)?;
+                for child in &fld.children {
+                    child.fmt(f)?;
+                }
+                match &fld.end {
+                    Some(end) => end.fmt(f),
+                    None => Ok(()),
+                }
+            }
+            Node::StrayEnd(end) => end.fmt(f),
+        }
+    }
+}
+
+impl<'a> fmt::Display for SyntaxTree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for tok in &self.prelude {
+            tok.fmt(f)?;
+        }
+        for node in &self.nodes {
+            node.fmt(f)?;
+        }
+        f.write_str(self.trailing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EntryType, Node, SyntaxTree};
+
+    const INPUT: &str = "Opera Hotlist version 2.0\r\n\
+    Options: encoding = utf8, version=3\r\n\
+    \r\n\
+    #FOLDER\r\n\
+    \tID=1\r\n\
+    \tNAME=Stuff\r\n\
+    \tCREATED=900000000\r\n\
+    \tUNIQUEID=11111111111111111111111111111111\r\n\
+    \r\n\
+    #NOTE \r\n\
+    \tNAME=first\r\n\
+    \tID=2\r\n\
+    \tUNIQUEID=22222222222222222222222222222222\r\n\
+    \tCREATED=1100000000\r\n\
+    \tNAME=Line one\x02\x02Line two\r\n\
+    \tPERSONALBAR_POS=0\r\n\
+    \r\n\
+    -\r\n\
+    \r\n\
+    #SEPARATOR\r\n\
+    \r\n\
+    -\r\n\
+    \n\
+    #NOTE\n\
+    \t ID=3\n\
+    \t NAME=\n\
+    \tUNIQUEID=33333333333333333333333333333333\n\
+    \tCREATED=1100000000\n\
+    \n\n";
+
+    #[test]
+    fn test_lossless() {
+        let tree = SyntaxTree::parse(INPUT).unwrap();
+        assert_eq!(tree.to_string(), INPUT);
+
+        let kinds: Vec<_> = tree
+            .nodes
+            .iter()
+            .map(|n| match n {
+                Node::Entry(e) => Some(e.kind),
+                Node::Folder(f) => Some(f.entry.kind),
+                Node::StrayEnd(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Some(EntryType::Folder),
+                Some(EntryType::Separator),
+                None,
+                Some(EntryType::Note)
+            ]
+        );
+
+        match &tree.nodes[0] {
+            Node::Folder(f) => {
+                let note = match &f.children[0] {
+                    Node::Entry(e) => e,
+                    _ => panic!("expected a note"),
+                };
+                let names: Vec<_> = note.fields.iter().map(|f| f.key()).collect();
+                assert_eq!(
+                    names,
+                    vec![
+                        "NAME",
+                        "ID",
+                        "UNIQUEID",
+                        "CREATED",
+                        "NAME",
+                        "PERSONALBAR_POS"
+                    ]
+                );
+                assert_eq!(
+                    note.field("NAME").unwrap().value(),
+                    Some("Line one\x02\x02Line two")
+                );
+                assert!(f.end.is_some());
+            }
+            _ => panic!("expected a folder"),
+        }
+    }
+
+    #[test]
+    fn test_lossless_unlexable_line() {
+        let input = INPUT.replace("\tPERSONALBAR_POS=0\r\n", "\tnot a field=0\r\n");
+        let tree = SyntaxTree::parse(&input).unwrap();
+        assert_eq!(tree.to_string(), input);
+
+        match &tree.nodes[0] {
+            Node::Folder(f) => match &f.children[0] {
+                Node::Entry(e) => {
+                    let last = e.fields.last().unwrap();
+                    assert_eq!(last.key(), "not a field=0");
+                    assert_eq!(last.value(), None);
+                }
+                _ => panic!("expected a note"),
+            },
+            _ => panic!("expected a folder"),
+        }
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut tree = SyntaxTree::parse(INPUT).unwrap();
+
+        let note = tree
+            .find_entry_mut(|e| {
+                e.field("UNIQUEID").and_then(|f| f.value())
+                    == Some("22222222222222222222222222222222")
+            })
+            .unwrap();
+        note.set_field("NAME", "Changed");
+        note.set_field("ACTIVE", "YES");
+        note.remove_field("PERSONALBAR_POS");
+
+        let empty = tree.find_entry_mut(|e| e.field("ID").and_then(|f| f.value()) == Some("3"));
+        empty.unwrap().set_field("NAME", "Filled in");
+
+        let expected = INPUT
+            .replace("Line one\x02\x02Line two", "Changed")
+            .replace("\tPERSONALBAR_POS=0\r\n", "\tACTIVE=YES\r\n")
+            .replace("\t NAME=\n", "\t NAME=Filled in\n");
+        assert_eq!(tree.to_string(), expected);
+    }
+}
@@ -0,0 +1,338 @@
+// Comparing two hotlists, e.g. backups of the same Opera profile, entry by entry. Entries are
+// matched by UUID, so an entry that was moved or edited is still recognized. An entry counts as
+// moved when its parent folder is a different one, not when a folder above it was renamed.
+// Separators aren't compared.
+
+use crate::ast::{decode_line_breaks, folder_path, walk, EntryKind, Hotlist, HotlistUrl};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EntryDiff {
+    pub uuid: Uuid,
+    // From the new hotlist, or the old one for removed entries.
+    pub id: u32,
+    pub kind: &'static str,
+    pub label: String,
+    pub change: Change,
+}
+
+// Folder paths are the names of the folders from the top level down.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change {
+    Added { folder: Vec<String> },
+    Removed { folder: Vec<String> },
+    Moved { from: Vec<String>, to: Vec<String> },
+    Modified(Vec<FieldChange>),
+}
+
+// Values are as they'd be written in the hotlist, with line breaks decoded. Flags are "YES" when
+// set, and None otherwise.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+// Changes in the order the entries appear in new, followed by the entries that were removed in the
+// order they appeared in old. An entry that was both moved and edited has two changes.
+pub fn diff(old: &Hotlist, new: &Hotlist) -> Vec<EntryDiff> {
+    let old_entries = index(old);
+    let new_entries = index(new);
+    let old_by_uuid: HashMap<Uuid, &Indexed> = old_entries.iter().map(|e| (e.uuid, e)).collect();
+    let new_uuids: HashSet<Uuid> = new_entries.iter().map(|e| e.uuid).collect();
+
+    let mut diffs = Vec::new();
+
+    for n in &new_entries {
+        let o = match old_by_uuid.get(&n.uuid) {
+            Some(o) => o,
+            None => {
+                diffs.push(n.diff(Change::Added {
+                    folder: n.folder.clone(),
+                }));
+                continue;
+            }
+        };
+
+        if o.parent != n.parent {
+            diffs.push(n.diff(Change::Moved {
+                from: o.folder.clone(),
+                to: n.folder.clone(),
+            }));
+        }
+
+        let fields = field_changes(o.entry, n.entry);
+        if !fields.is_empty() {
+            diffs.push(n.diff(Change::Modified(fields)));
+        }
+    }
+
+    for o in &old_entries {
+        if !new_uuids.contains(&o.uuid) {
+            diffs.push(o.diff(Change::Removed {
+                folder: o.folder.clone(),
+            }));
+        }
+    }
+
+    diffs
+}
+
+struct Indexed<'h, 'a> {
+    uuid: Uuid,
+    entry: &'h EntryKind<'a>,
+    // None at the top level.
+    parent: Option<Uuid>,
+    folder: Vec<String>,
+}
+
+impl<'h, 'a> Indexed<'h, 'a> {
+    fn diff(&self, change: Change) -> EntryDiff {
+        EntryDiff {
+            uuid: self.uuid,
+            id: self.entry.id().unwrap_or(0),
+            kind: self.entry.kind(),
+            label: self.entry.label(),
+            change,
+        }
+    }
+}
+
+// Entries with a UUID in tree order. If a UUID is used twice, only the first entry counts.
+fn index<'h, 'a>(hl: &'h Hotlist<'a>) -> Vec<Indexed<'h, 'a>> {
+    let mut out = Vec::new();
+    walk(&hl.entries, |folders, e| {
+        let uuid = match (e, e.uuid()) {
+            (EntryKind::Separator(_), _) | (_, None) => return,
+            (_, Some(uuid)) => uuid,
+        };
+
+        out.push(Indexed {
+            uuid,
+            entry: e,
+            parent: folders.last().map(|f| f.uuid),
+            folder: folders.iter().map(|f| f.name.to_string()).collect(),
+        });
+    });
+
+    let mut seen = HashSet::new();
+    out.retain(|e| seen.insert(e.uuid));
+    out
+}
+
+fn field_changes(old: &EntryKind, new: &EntryKind) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut cmp = |field, old: Option<String>, new: Option<String>| {
+        if old != new {
+            changes.push(FieldChange { field, old, new });
+        }
+    };
+
+    match (old, new) {
+        (EntryKind::Folder(o), EntryKind::Folder(n)) => {
+            cmp("name", Some(o.name.to_string()), Some(n.name.to_string()));
+            cmp("expanded", flag(o.expanded), flag(n.expanded));
+            cmp("trash", flag(o.trash), flag(n.trash));
+        }
+        (EntryKind::Note(o), EntryKind::Note(n)) => {
+            cmp("contents", text(&o.contents), text(&n.contents));
+            cmp("url", url(&o.url), url(&n.url));
+            cmp("active", flag(o.active), flag(n.active));
+        }
+        (EntryKind::Bookmark(o), EntryKind::Bookmark(n)) => {
+            cmp("name", Some(o.name.to_string()), Some(n.name.to_string()));
+            cmp("url", url(&o.url), url(&n.url));
+            cmp("description", text(&o.description), text(&n.description));
+            cmp("active", flag(o.active), flag(n.active));
+        }
+        (EntryKind::Contact(o), EntryKind::Contact(n)) => {
+            cmp("name", Some(o.name.to_string()), Some(n.name.to_string()));
+            cmp("url", url(&o.url), url(&n.url));
+            cmp("description", text(&o.description), text(&n.description));
+            cmp("active", flag(o.active), flag(n.active));
+        }
+        _ => cmp("kind", Some(old.kind().into()), Some(new.kind().into())),
+    }
+
+    changes
+}
+
+fn text<S: AsRef<str>>(s: &Option<S>) -> Option<String> {
+    s.as_ref()
+        .map(|s| decode_line_breaks(s.as_ref()).into_owned())
+}
+
+fn url(u: &Option<HotlistUrl>) -> Option<String> {
+    u.as_ref().map(HotlistUrl::to_string)
+}
+
+fn flag(set: bool) -> Option<String> {
+    if set {
+        Some("YES".to_string())
+    } else {
+        None
+    }
+}
+
+impl fmt::Display for EntryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = format!("{} {} {:?}", self.kind, self.id, self.label);
+        match &self.change {
+            Change::Added { folder } => write!(f, "added {} in {}", what, folder_path(folder)),
+            Change::Removed { folder } => {
+                write!(f, "removed {} from {}", what, folder_path(folder))
+            }
+            Change::Moved { from, to } => {
+                write!(
+                    f,
+                    "moved {} from {} to {}",
+                    what,
+                    folder_path(from),
+                    folder_path(to)
+                )
+            }
+            Change::Modified(fields) => {
+                write!(f, "modified {}:", what)?;
+                for c in fields {
+                    let show = |v: &Option<String>| match v {
+                        Some(v) => format!("{:?}", v),
+                        None => "(none)".to_string(),
+                    };
+                    write!(f, "\n    {}: {} -> {}", c.field, show(&c.old), show(&c.new))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl EntryDiff {
+    pub fn to_json(&self) -> Value {
+        let mut obj = json!({
+            "uuid": self.uuid.to_hyphenated_ref().to_string(),
+            "id": self.id,
+            "kind": self.kind,
+            "label": self.label,
+        });
+
+        let change = match &self.change {
+            Change::Added { folder } => json!({ "change": "added", "folder": folder }),
+            Change::Removed { folder } => json!({ "change": "removed", "folder": folder }),
+            Change::Moved { from, to } => json!({ "change": "moved", "from": from, "to": to }),
+            Change::Modified(fields) => {
+                let fields: Vec<Value> = fields
+                    .iter()
+                    .map(|c| json!({ "field": c.field, "old": c.old, "new": c.new }))
+                    .collect();
+                json!({ "change": "modified", "fields": fields })
+            }
+        };
+
+        if let (Value::Object(obj), Value::Object(change)) = (&mut obj, change) {
+            obj.extend(change);
+        }
+        obj
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Change, FieldChange};
+    use crate::ast::{EntryKind, Note};
+    use crate::test_support::hotlist;
+
+    #[test]
+    fn test_unchanged() {
+        let hl = hotlist();
+        assert!(diff(&hl, &hl).is_empty());
+    }
+
+    #[test]
+    fn test_modified() {
+        let old = hotlist();
+        let mut new = hotlist();
+        match new.entry_mut(7) {
+            Some(EntryKind::Note(n)) => {
+                n.contents = Some("Pancakes\x02\x02Bake.".into());
+                n.url = None;
+            }
+            e => panic!("unexpected entry {:?}", e),
+        }
+
+        let diffs = diff(&old, &new);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!((diffs[0].id, &*diffs[0].label), (7, "Pancakes"));
+        assert_eq!(
+            diffs[0].change,
+            Change::Modified(vec![
+                FieldChange {
+                    field: "contents",
+                    old: Some("Pancakes\nTwo cups of flour, one egg and milk.\nFry.".into()),
+                    new: Some("Pancakes\nBake.".into()),
+                },
+                FieldChange {
+                    field: "url",
+                    old: Some("https://example.com/pancakes".into()),
+                    new: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_moved() {
+        let old = hotlist();
+        let mut new = hotlist();
+        new.move_entry(5, None).unwrap();
+
+        let diffs = diff(&old, &new);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].change,
+            Change::Moved {
+                from: vec!["Work".into()],
+                to: vec![],
+            }
+        );
+        assert_eq!(
+            diffs[0].to_string(),
+            "moved note 5 \"Ticket.\" from /Work to /"
+        );
+    }
+
+    // Added entries come in the new hotlist's order, then removed ones in the old one's.
+    #[test]
+    fn test_added_and_removed() {
+        let old = hotlist();
+        let mut new = hotlist();
+        new.insert(None, EntryKind::Note(Note::new("New.")))
+            .unwrap();
+        new.remove(10).unwrap();
+
+        let diffs = diff(&old, &new);
+        let summary: Vec<_> = diffs.iter().map(|d| (d.id, &d.change)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (11, &Change::Added { folder: vec![] }),
+                (
+                    10,
+                    &Change::Removed {
+                        folder: vec!["Trash".into()]
+                    }
+                ),
+            ]
+        );
+        assert_eq!(diffs[0].to_json()["change"], "added");
+        assert_eq!(
+            diffs[1].to_string(),
+            "removed note 10 \"Deleted.\" from /Trash"
+        );
+    }
+}
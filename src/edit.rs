@@ -0,0 +1,429 @@
+// Changing a parsed hotlist from code. Entries are found by ID, UUID or folder path. New entries
+// get IDs after the highest one in use and fresh UUIDs, and there is never more than one trash
+// folder, which stays at the top level like Opera keeps it.
+
+use crate::ast::{walk, EntryKind, Folder, Hotlist, Note};
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+
+use chrono::{DateTime, TimeZone, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EditError {
+    NoSuchEntry(u32),
+    NotAFolder(u32),
+    // Moving a folder into itself or one of its subfolders.
+    MoveIntoSelf(u32),
+    SecondTrashFolder,
+    TrashNotAtTop,
+    // Every ID after the highest one in use is taken, up to u32::MAX.
+    OutOfIds,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::NoSuchEntry(id) => write!(f, "no entry with ID {}", id),
+            EditError::NotAFolder(id) => write!(f, "entry {} is not a folder", id),
+            EditError::MoveIntoSelf(id) => write!(f, "folder {} can't be moved into itself", id),
+            EditError::SecondTrashFolder => write!(f, "hotlist already has a trash folder"),
+            EditError::TrashNotAtTop => write!(f, "the trash folder must be at the top level"),
+            EditError::OutOfIds => write!(f, "no IDs left after the highest one in use"),
+        }
+    }
+}
+
+impl error::Error for EditError {}
+
+impl<'a> Note<'a> {
+    // ID and UUID are filled in by Hotlist::insert.
+    pub fn new<T: Into<Cow<'a, str>>>(contents: T) -> Self {
+        Note {
+            id: 0,
+            uuid: Uuid::nil(),
+            contents: Some(contents.into()),
+            url: None,
+            timestamp: now(),
+            active: false,
+            extra: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Folder<'a> {
+    // ID and UUID are filled in by Hotlist::insert.
+    pub fn new<T: Into<Cow<'a, str>>>(name: T) -> Self {
+        Folder {
+            id: 0,
+            uuid: Uuid::nil(),
+            name: name.into(),
+            timestamp: now(),
+            trash: false,
+            expanded: false,
+            extra: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Hotlist<'a> {
+    pub fn entry(&self, id: u32) -> Option<&EntryKind<'a>> {
+        let path = find(&self.entries, &|e| e.id() == Some(id))?;
+        Some(entry_at(&self.entries, &path))
+    }
+
+    pub fn entry_mut(&mut self, id: u32) -> Option<&mut EntryKind<'a>> {
+        let path = find(&self.entries, &|e| e.id() == Some(id))?;
+        Some(entry_at_mut(&mut self.entries, &path))
+    }
+
+    pub fn entry_by_uuid(&self, uuid: &Uuid) -> Option<&EntryKind<'a>> {
+        let path = find(&self.entries, &|e| e.uuid().as_ref() == Some(uuid))?;
+        Some(entry_at(&self.entries, &path))
+    }
+
+    pub fn entry_by_uuid_mut(&mut self, uuid: &Uuid) -> Option<&mut EntryKind<'a>> {
+        let path = find(&self.entries, &|e| e.uuid().as_ref() == Some(uuid))?;
+        Some(entry_at_mut(&mut self.entries, &path))
+    }
+
+    // Folder names from the top level down. Where siblings share a name, the first one wins.
+    pub fn folder_by_path(&self, path: &[&str]) -> Option<&Folder<'a>> {
+        let (first, rest) = path.split_first()?;
+        let mut folder = child_folder(&self.entries, first)?;
+        for name in rest {
+            folder = child_folder(&folder.entries, name)?;
+        }
+        Some(folder)
+    }
+
+    pub fn trash_folder(&self) -> Option<&Folder<'a>> {
+        self.entries.iter().find_map(|e| match e {
+            EntryKind::Folder(f) if f.trash => Some(f),
+            _ => None,
+        })
+    }
+
+    // One more than the highest ID anywhere in the hotlist, or None if that's u32::MAX.
+    pub fn next_id(&self) -> Option<u32> {
+        let mut max_id = None;
+        walk(&self.entries, |_, e| max_id = max_id.max(e.id()));
+
+        match max_id {
+            Some(id) => id.checked_add(1),
+            None => Some(1),
+        }
+    }
+
+    // Adds an entry at the end of a folder, or of the top level if parent is None. The entry, and
+    // everything in it if it's a folder, gets new IDs and UUIDs. Returns the entry's new ID.
+    pub fn insert(
+        &mut self,
+        parent: Option<u32>,
+        mut entry: EntryKind<'a>,
+    ) -> Result<u32, EditError> {
+        if has_trash(std::slice::from_ref(&entry)) {
+            if parent.is_some() {
+                return Err(EditError::TrashNotAtTop);
+            }
+            if self.trash_folder().is_some() {
+                return Err(EditError::SecondTrashFolder);
+            }
+        }
+
+        let mut next_id = self.next_id();
+        let id = next_id.ok_or(EditError::OutOfIds)?;
+        renumber(&mut entry, &mut next_id)?;
+
+        self.folder_entries_mut(parent)?.push(entry);
+        Ok(id)
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<EntryKind<'a>, EditError> {
+        let path =
+            find(&self.entries, &|e| e.id() == Some(id)).ok_or(EditError::NoSuchEntry(id))?;
+        let (last, parent) = path.split_last().unwrap();
+        Ok(entries_at_mut(&mut self.entries, parent).remove(*last))
+    }
+
+    // Moves an entry to the end of another folder, or of the top level. It keeps its ID and UUID.
+    pub fn move_entry(&mut self, id: u32, parent: Option<u32>) -> Result<(), EditError> {
+        let from =
+            find(&self.entries, &|e| e.id() == Some(id)).ok_or(EditError::NoSuchEntry(id))?;
+
+        if let Some(parent_id) = parent {
+            let to = find(&self.entries, &|e| e.id() == Some(parent_id))
+                .ok_or(EditError::NoSuchEntry(parent_id))?;
+            match entry_at(&self.entries, &to) {
+                EntryKind::Folder(_) => {}
+                _ => return Err(EditError::NotAFolder(parent_id)),
+            }
+            if to.starts_with(&from) {
+                return Err(EditError::MoveIntoSelf(id));
+            }
+            if has_trash(std::slice::from_ref(entry_at(&self.entries, &from))) {
+                return Err(EditError::TrashNotAtTop);
+            }
+        }
+
+        let entry = self.remove(id)?;
+        self.folder_entries_mut(parent)?.push(entry);
+        Ok(())
+    }
+
+    // Deletes everything in the trash folder for good, and returns it.
+    pub fn empty_trash(&mut self) -> Vec<EntryKind<'a>> {
+        let trash = self.entries.iter_mut().find_map(|e| match e {
+            EntryKind::Folder(f) if f.trash => Some(f),
+            _ => None,
+        });
+
+        match trash {
+            Some(f) => std::mem::take(&mut f.entries),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn folder_entries_mut(
+        &mut self,
+        id: Option<u32>,
+    ) -> Result<&mut Vec<EntryKind<'a>>, EditError> {
+        let id = match id {
+            Some(id) => id,
+            None => return Ok(&mut self.entries),
+        };
+
+        match self.entry_mut(id) {
+            Some(EntryKind::Folder(f)) => Ok(&mut f.entries),
+            Some(_) => Err(EditError::NotAFolder(id)),
+            None => Err(EditError::NoSuchEntry(id)),
+        }
+    }
+}
+
+// The hotlist format only stores whole seconds.
+fn now() -> DateTime<Utc> {
+    Utc.timestamp(Utc::now().timestamp(), 0)
+}
+
+// Indices from the top level down to the first entry that matches.
+fn find<F>(entries: &[EntryKind], pred: &F) -> Option<Vec<usize>>
+where
+    F: Fn(&EntryKind) -> bool,
+{
+    for (i, e) in entries.iter().enumerate() {
+        if pred(e) {
+            return Some(vec![i]);
+        }
+        if let EntryKind::Folder(f) = e {
+            if let Some(mut path) = find(&f.entries, pred) {
+                path.insert(0, i);
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+fn entry_at<'e, 'a>(entries: &'e [EntryKind<'a>], path: &[usize]) -> &'e EntryKind<'a> {
+    let (last, parent) = path.split_last().unwrap();
+    let mut entries = entries;
+    for i in parent {
+        match &entries[*i] {
+            EntryKind::Folder(f) => entries = &f.entries,
+            _ => unreachable!("paths only go through folders"),
+        }
+    }
+    &entries[*last]
+}
+
+fn entry_at_mut<'e, 'a>(
+    entries: &'e mut Vec<EntryKind<'a>>,
+    path: &[usize],
+) -> &'e mut EntryKind<'a> {
+    let (last, parent) = path.split_last().unwrap();
+    &mut entries_at_mut(entries, parent)[*last]
+}
+
+// The entries of the folder at path, or the top level for an empty path.
+fn entries_at_mut<'e, 'a>(
+    entries: &'e mut Vec<EntryKind<'a>>,
+    path: &[usize],
+) -> &'e mut Vec<EntryKind<'a>> {
+    let mut entries = entries;
+    for i in path {
+        match &mut entries[*i] {
+            EntryKind::Folder(f) => entries = &mut f.entries,
+            _ => unreachable!("paths only go through folders"),
+        }
+    }
+    entries
+}
+
+fn child_folder<'e, 'a>(entries: &'e [EntryKind<'a>], name: &str) -> Option<&'e Folder<'a>> {
+    entries.iter().find_map(|e| match e {
+        EntryKind::Folder(f) if f.name == name => Some(f),
+        _ => None,
+    })
+}
+
+fn has_trash(entries: &[EntryKind]) -> bool {
+    let mut trash = false;
+    walk(entries, |_, e| {
+        trash |= matches!(e, EntryKind::Folder(f) if f.trash)
+    });
+    trash
+}
+
+// next_id is None once u32::MAX has been handed out.
+fn renumber(entry: &mut EntryKind, next_id: &mut Option<u32>) -> Result<(), EditError> {
+    let id = next_id.ok_or(EditError::OutOfIds)?;
+    *next_id = id.checked_add(1);
+    let uuid = Uuid::new_v4();
+
+    match entry {
+        EntryKind::Folder(f) => {
+            f.id = id;
+            f.uuid = uuid;
+            for e in &mut f.entries {
+                renumber(e, next_id)?;
+            }
+        }
+        EntryKind::Note(n) => {
+            n.id = id;
+            n.uuid = uuid;
+        }
+        EntryKind::Bookmark(b) => {
+            b.id = id;
+            b.uuid = uuid;
+        }
+        EntryKind::Contact(c) => {
+            c.id = id;
+            c.uuid = uuid;
+        }
+        EntryKind::Separator(s) => {
+            s.id = Some(id);
+            s.uuid = Some(uuid);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EditError;
+    use crate::ast::{EntryKind, Folder, Note};
+    use crate::test_support::{hotlist, ids};
+
+    #[test]
+    fn test_find() {
+        let hl = hotlist();
+        assert_eq!(hl.folder_by_path(&["Work", "Old"]).unwrap().id, 3);
+        assert!(hl.folder_by_path(&["Old"]).is_none());
+        assert_eq!(hl.trash_folder().unwrap().id, 9);
+        assert_eq!(hl.next_id(), Some(11));
+
+        let uuid = hl.entry(4).and_then(|e| e.uuid()).unwrap();
+        assert_eq!(hl.entry_by_uuid(&uuid).and_then(|e| e.id()), Some(4));
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut hl = hotlist();
+        let id = hl
+            .insert(Some(3), EntryKind::Note(Note::new("Bar.")))
+            .unwrap();
+        assert_eq!(id, 11);
+        assert_eq!(
+            ids(&hl.folder_by_path(&["Work", "Old"]).unwrap().entries),
+            vec![4, 11]
+        );
+
+        let uuid = hl.entry(id).and_then(|e| e.uuid()).unwrap();
+        assert!(!uuid.is_nil());
+        assert_eq!(hl.entry_by_uuid(&uuid).and_then(|e| e.id()), Some(11));
+
+        assert_eq!(
+            hl.insert(Some(4), EntryKind::Note(Note::new("Baz."))),
+            Err(EditError::NotAFolder(4))
+        );
+    }
+
+    // A whole folder is numbered depth-first.
+    #[test]
+    fn test_insert_folder() {
+        let mut hl = hotlist();
+        let mut f = Folder::new("New");
+        f.entries.push(EntryKind::Note(Note::new("Baz.")));
+        assert_eq!(hl.insert(None, EntryKind::Folder(f)), Ok(11));
+        assert_eq!(ids(&hl.folder_by_path(&["New"]).unwrap().entries), vec![12]);
+    }
+
+    #[test]
+    fn test_move() {
+        let mut hl = hotlist();
+        assert_eq!(hl.move_entry(2, Some(3)), Err(EditError::MoveIntoSelf(2)));
+        assert_eq!(hl.move_entry(3, Some(4)), Err(EditError::NotAFolder(4)));
+
+        hl.move_entry(3, None).unwrap();
+        assert!(hl.folder_by_path(&["Work", "Old"]).is_none());
+        assert_eq!(ids(&hl.folder_by_path(&["Old"]).unwrap().entries), vec![4]);
+    }
+
+    #[test]
+    fn test_one_trash_folder() {
+        let mut hl = hotlist();
+        assert_eq!(hl.move_entry(9, Some(2)), Err(EditError::TrashNotAtTop));
+
+        let mut trash = Folder::new("Trash");
+        trash.trash = true;
+        assert_eq!(
+            hl.insert(None, EntryKind::Folder(trash)),
+            Err(EditError::SecondTrashFolder)
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut hl = hotlist();
+        let removed = hl.remove(3).unwrap();
+        assert_eq!(ids(std::slice::from_ref(&removed)), vec![3, 4]);
+        assert!(hl.entry(4).is_none());
+        assert_eq!(hl.remove(3).unwrap_err(), EditError::NoSuchEntry(3));
+
+        let deleted = hl.empty_trash();
+        assert_eq!(ids(&deleted), vec![10]);
+        assert!(hl.trash_folder().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_out_of_ids() {
+        let mut hl = hotlist();
+        match hl.entry_mut(10) {
+            Some(EntryKind::Note(n)) => n.id = u32::MAX - 1,
+            e => panic!("unexpected entry {:?}", e),
+        }
+
+        // A folder and its note need two IDs, but only u32::MAX is left.
+        let mut f = Folder::new("New");
+        f.entries.push(EntryKind::Note(Note::new("Baz.")));
+        assert_eq!(
+            hl.insert(None, EntryKind::Folder(f)),
+            Err(EditError::OutOfIds)
+        );
+        assert!(hl.folder_by_path(&["New"]).is_none());
+
+        let id = hl.insert(None, EntryKind::Note(Note::new("Bar.")));
+        assert_eq!(id, Ok(u32::MAX));
+        assert_eq!(hl.next_id(), None);
+        assert_eq!(
+            hl.insert(None, EntryKind::Note(Note::new("Qux."))),
+            Err(EditError::OutOfIds)
+        );
+    }
+}
@@ -1,19 +1,59 @@
+mod adr;
 mod html;
 mod tiddler_json;
 
 // Re-exports
+pub use adr::emit as emit_hotlist_as_adr;
+pub use adr::write as write_hotlist_as_adr;
 pub use html::emit as emit_hotlist_as_html;
+pub use html::write as write_hotlist_as_html;
 pub use tiddler_json::emit as emit_hotlist_as_tiddler_json;
+pub use tiddler_json::write as write_hotlist_as_tiddler_json;
 
 // Imports
-use crate::ast::{EntryKind, Folder, Hotlist, Note};
+use crate::ast::{Bookmark, Contact, EntryKind, Folder, Hotlist, Note, Separator};
 use crate::error::Error;
 
+// What the HTML and Tiddler JSON emitters do with the contents of the trash folder. With Separate,
+// they're kept apart from everything else: in their own section of a single HTML file, their own
+// directory next to the multiple-file output, or under their own tag as tiddlers.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum TrashMode {
+    #[default]
+    Include,
+    Exclude,
+    Separate,
+}
+
+// Whether a traversal is in the trash folder. Every folder has to be entered and left, whether or
+// not it's the trash.
+#[derive(Debug, Default)]
+struct TrashDepth(usize);
+
+impl TrashDepth {
+    fn enter(&mut self, f: &Folder) {
+        if self.0 > 0 || f.trash {
+            self.0 += 1;
+        }
+    }
+
+    fn leave(&mut self) {
+        self.0 = self.0.saturating_sub(1);
+    }
+
+    fn inside(&self) -> bool {
+        self.0 > 0
+    }
+}
+
 trait Visitor<'ast, 'input> {
     fn visit_folder_empty(&mut self, folder: &'ast Folder<'input>) -> Result<(), Error<'static>>;
     fn visit_folder_pre(&mut self, folder: &'ast Folder<'input>) -> Result<(), Error<'static>>;
     fn visit_folder_post(&mut self, folder: &'ast Folder<'input>) -> Result<(), Error<'static>>;
     fn visit_note(&mut self, note: &'ast Note<'input>) -> Result<(), Error<'static>>;
+    fn visit_bookmark(&mut self, bookmark: &'ast Bookmark<'input>) -> Result<(), Error<'static>>;
+    fn visit_contact(&mut self, contact: &'ast Contact<'input>) -> Result<(), Error<'static>>;
+    fn visit_separator(&mut self, separator: &'ast Separator) -> Result<(), Error<'static>>;
     fn visit_root_pre(&mut self, hotlist: &'ast Hotlist<'input>) -> Result<(), Error<'static>>;
     fn visit_root_post(&mut self, hotlist: &'ast Hotlist<'input>) -> Result<(), Error<'static>>;
 }
@@ -61,6 +101,24 @@ fn traverse_hotlist<'ast, 'input, V: Visitor<'ast, 'input>>(
             EntryKind::Note(n) => {
                 visitor.visit_note(n)?;
 
+                last_visited = Some(curr);
+                stack.pop();
+            }
+            EntryKind::Bookmark(b) => {
+                visitor.visit_bookmark(b)?;
+
+                last_visited = Some(curr);
+                stack.pop();
+            }
+            EntryKind::Contact(c) => {
+                visitor.visit_contact(c)?;
+
+                last_visited = Some(curr);
+                stack.pop();
+            }
+            EntryKind::Separator(s) => {
+                visitor.visit_separator(s)?;
+
                 last_visited = Some(curr);
                 stack.pop();
             }
@@ -0,0 +1,340 @@
+use super::traverse_hotlist;
+use super::Visitor;
+use crate::ast::{Bookmark, Contact, Encoding, Folder, Hotlist, HotlistUrl, Note, Separator};
+use crate::error::Error;
+
+use std::borrow::Cow;
+use std::fmt::{self, Display, Write as FmtWrite};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+pub fn emit<T: AsRef<Path>>(
+    filename: Option<T>,
+    hl: &Hotlist,
+    multi: bool,
+) -> Result<(), Error<'static>> {
+    if multi {
+        // TODO: EmitError
+        return Err("multiple-file output is not supported for adr".into());
+    }
+
+    let out_handle: Box<dyn Write> = if let Some(fn_) = filename {
+        let file = File::create(fn_.as_ref())?;
+        Box::new(BufWriter::new(file))
+    } else {
+        Box::new(BufWriter::new(io::stdout()))
+    };
+
+    write(out_handle, hl)
+}
+
+pub fn write<W: Write>(out: W, hl: &Hotlist) -> Result<(), Error<'static>> {
+    let mut emitter = AdrEmitter::new(out);
+    traverse_hotlist(hl, &mut emitter)?;
+    let mut out = emitter.into_inner();
+    out.flush()?;
+
+    Ok(())
+}
+
+// Writes a hotlist the way Opera does, so that it can be imported again. Parsing the output gives
+// back the same Hotlist, though unrecognized fields move to the end of their entry.
+pub struct AdrEmitter<W>
+where
+    W: Write,
+{
+    out: W,
+    // Legacy hotlists are written back in their own code page, so the text is collected first and
+    // encoded once at the end.
+    buf: String,
+    encoding: &'static encoding_rs::Encoding,
+}
+
+impl<W> AdrEmitter<W>
+where
+    W: Write,
+{
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            buf: String::new(),
+            encoding: encoding_rs::UTF_8,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn header(&mut self, header: &str) -> fmt::Result {
+        writeln!(self.buf, "{}", header)
+    }
+
+    fn field<D: Display>(&mut self, key: &str, value: D) -> fmt::Result {
+        writeln!(self.buf, "\t{}={}", key, value)
+    }
+
+    fn opt_field<D: Display>(&mut self, key: &str, value: Option<D>) -> fmt::Result {
+        match value {
+            Some(v) => self.field(key, v),
+            None => Ok(()),
+        }
+    }
+
+    // Opera only writes yes/no fields when they're set.
+    fn flag_field(&mut self, key: &str, value: bool) -> fmt::Result {
+        if value {
+            self.field(key, "YES")
+        } else {
+            Ok(())
+        }
+    }
+
+    fn uuid_field(&mut self, uuid: &Uuid) -> fmt::Result {
+        self.field("UNIQUEID", format_args!("{:X}", uuid.to_simple_ref()))
+    }
+
+    fn timestamp_field(&mut self, key: &str, timestamp: &DateTime<Utc>) -> fmt::Result {
+        self.field(key, timestamp.timestamp())
+    }
+
+    fn url_field(&mut self, url: &Option<HotlistUrl>) -> fmt::Result {
+        self.opt_field("URL", url.as_ref())
+    }
+
+    fn extra_fields(&mut self, extra: &[(Cow<str>, Cow<str>)]) -> fmt::Result {
+        for (k, v) in extra {
+            self.field(k, v)?;
+        }
+        Ok(())
+    }
+
+    fn folder_fields(&mut self, f: &Folder) -> fmt::Result {
+        self.header("#FOLDER")?;
+        self.field("ID", f.id)?;
+        self.field("NAME", &f.name)?;
+        self.timestamp_field("CREATED", &f.timestamp)?;
+        self.flag_field("TRASH FOLDER", f.trash)?;
+        self.flag_field("EXPANDED", f.expanded)?;
+        self.uuid_field(&f.uuid)?;
+        self.extra_fields(&f.extra)?;
+        writeln!(self.buf)
+    }
+
+    fn folder_end(&mut self) -> fmt::Result {
+        write!(self.buf, "-\n\n")
+    }
+}
+
+impl<'ast, 'input, W> Visitor<'ast, 'input> for AdrEmitter<W>
+where
+    W: Write,
+{
+    fn visit_folder_empty(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
+        self.folder_fields(f)?;
+        self.folder_end()?;
+        Ok(())
+    }
+
+    fn visit_folder_pre(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
+        self.folder_fields(f)?;
+        Ok(())
+    }
+
+    fn visit_folder_post(&mut self, _f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
+        self.folder_end()?;
+        Ok(())
+    }
+
+    fn visit_note(&mut self, n: &'ast Note<'input>) -> Result<(), Error<'static>> {
+        self.header("#NOTE")?;
+        self.field("ID", n.id)?;
+        self.uuid_field(&n.uuid)?;
+        // Line breaks are still "\x02\x02" pairs; the parser keeps them as-is.
+        self.opt_field("NAME", n.contents.as_deref())?;
+        self.url_field(&n.url)?;
+        self.timestamp_field("CREATED", &n.timestamp)?;
+        self.flag_field("ACTIVE", n.active)?;
+        self.extra_fields(&n.extra)?;
+        writeln!(self.buf)?;
+        Ok(())
+    }
+
+    fn visit_bookmark(&mut self, b: &'ast Bookmark<'input>) -> Result<(), Error<'static>> {
+        self.header("#URL")?;
+        self.field("ID", b.id)?;
+        self.field("NAME", &b.name)?;
+        self.url_field(&b.url)?;
+        self.timestamp_field("CREATED", &b.timestamp)?;
+        if let Some(v) = &b.visited {
+            self.timestamp_field("VISITED", v)?;
+        }
+        self.opt_field("DESCRIPTION", b.description.as_deref())?;
+        self.opt_field("SHORT NAME", b.short_name.as_deref())?;
+        self.opt_field("ICONFILE", b.icon_file.as_deref())?;
+        self.flag_field("ACTIVE", b.active)?;
+        self.uuid_field(&b.uuid)?;
+        self.extra_fields(&b.extra)?;
+        writeln!(self.buf)?;
+        Ok(())
+    }
+
+    fn visit_contact(&mut self, c: &'ast Contact<'input>) -> Result<(), Error<'static>> {
+        self.header("#CONTACT")?;
+        self.field("ID", c.id)?;
+        self.field("NAME", &c.name)?;
+        self.url_field(&c.url)?;
+        self.timestamp_field("CREATED", &c.timestamp)?;
+        self.opt_field("DESCRIPTION", c.description.as_deref())?;
+        self.opt_field("SHORT NAME", c.short_name.as_deref())?;
+        self.flag_field("ACTIVE", c.active)?;
+        self.opt_field("MAIL", c.mail.as_deref())?;
+        self.opt_field("PHONE", c.phone.as_deref())?;
+        self.opt_field("FAX", c.fax.as_deref())?;
+        self.opt_field("POSTALADDRESS", c.postal_address.as_deref())?;
+        self.opt_field("PICTUREURL", c.picture_url.as_deref())?;
+        self.opt_field("ICON", c.icon.as_deref())?;
+        self.uuid_field(&c.uuid)?;
+        self.extra_fields(&c.extra)?;
+        writeln!(self.buf)?;
+        Ok(())
+    }
+
+    fn visit_separator(&mut self, s: &'ast Separator) -> Result<(), Error<'static>> {
+        self.header("#SEPARATOR")?;
+        self.opt_field("ID", s.id)?;
+        if let Some(u) = &s.uuid {
+            self.uuid_field(u)?;
+        }
+        self.extra_fields(&s.extra)?;
+        writeln!(self.buf)?;
+        Ok(())
+    }
+
+    fn visit_root_pre(&mut self, hl: &'ast Hotlist<'input>) -> Result<(), Error<'static>> {
+        writeln!(self.buf, "Opera Hotlist version {}", hl.version.as_str())?;
+
+        match &hl.options.encoding {
+            Encoding::Utf8(v) => {
+                writeln!(self.buf, "Options: encoding = utf8, version={}", v.as_str())?;
+                self.encoding = encoding_rs::UTF_8;
+            }
+            // No Options line, same as Opera 5/6.
+            Encoding::Legacy(e) => {
+                self.encoding = *e;
+            }
+        }
+
+        writeln!(self.buf)?;
+        Ok(())
+    }
+
+    fn visit_root_post(&mut self, hl: &'ast Hotlist<'input>) -> Result<(), Error<'static>> {
+        let legacy = matches!(hl.options.encoding, Encoding::Legacy(_));
+
+        if self.encoding.output_encoding() == encoding_rs::UTF_8 {
+            // Without an Options line, only a BOM tells the parser this is UTF-8.
+            if legacy {
+                self.out.write_all(b"\xEF\xBB\xBF")?;
+            }
+            self.out.write_all(self.buf.as_bytes())?;
+        } else {
+            let (bytes, _, unmappable) = self.encoding.encode(&self.buf);
+            if unmappable {
+                return Err(format!(
+                    "hotlist contains characters that can't be written in {}",
+                    self.encoding.name()
+                )
+                .into());
+            }
+            self.out.write_all(&bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AdrEmitter;
+    use crate::gen::traverse_hotlist;
+    use crate::lexer;
+    use crate::parser::{hotlist, ParseContext};
+
+    #[test]
+    fn test_round_trip() {
+        let inp = "Opera Hotlist version 2.0\n\
+        Options: encoding = utf8, version=3\n\
+        \n\
+        #FOLDER\n\
+        \tID=1\n\
+        \tNAME=Stuff\n\
+        \tCREATED=900000000\n\
+        \tEXPANDED=YES\n\
+        \tUNIQUEID=11111111111111111111111111111111\n\
+        \tDESCRIPTION=Things\n\
+        \n\
+        #URL\n\
+        \tID=2\n\
+        \tNAME=Opera <Software>\n\
+        \tURL=http://www.opera.com/\n\
+        \tCREATED=1195742106\n\
+        \tVISITED=1322363353\n\
+        \tDESCRIPTION=Home\x02\x02page\n\
+        \tUNIQUEID=E4A8A3A0F7B011DCB1E9E5A2AC3B3C6E\n\
+        \tPERSONALBAR_POS=0\n\
+        \n\
+        #FOLDER\n\
+        \tID=3\n\
+        \tNAME=Empty\n\
+        \tCREATED=-86400\n\
+        \tUNIQUEID=33333333333333333333333333333333\n\
+        \n\
+        -\n\
+        \n\
+        -\n\
+        \n\
+        #SEPARATOR\n\
+        \n\
+        #NOTE\n\
+        \tID=4\n\
+        \tUNIQUEID=22222222222222222222222222222222\n\
+        \tNAME=Line one\x02\x02Line two\n\
+        \tURL=https://example.com/a?b=c\n\
+        \tCREATED=1100000000\n\
+        \tACTIVE=YES\n\
+        \n\
+        #CONTACT\n\
+        \tID=5\n\
+        \tNAME=Jane Doe\n\
+        \tCREATED=1100000000\n\
+        \tMAIL=jane@example.com\n\
+        \tPHONE=555-0100\n\
+        \tUNIQUEID=3B3A1D28C6E5D911A2A6F4C9BDE08B57\n\
+        \n\
+        #SEPARATOR\n\
+        \tID=6\n\
+        \tUNIQUEID=44444444444444444444444444444444\n\
+        \n";
+
+        let lexer = lexer::Lexer::new(inp);
+        let hl = hotlist::HotlistParser::new()
+            .parse(inp, &mut ParseContext::default(), lexer)
+            .unwrap();
+
+        let mut emitter = AdrEmitter::new(Vec::new());
+        traverse_hotlist(&hl, &mut emitter).unwrap();
+        let out = String::from_utf8(emitter.into_inner()).unwrap();
+        assert_eq!(out, inp);
+
+        let lexer = lexer::Lexer::new(&out);
+        let hl2 = hotlist::HotlistParser::new()
+            .parse(&out, &mut ParseContext::default(), lexer)
+            .unwrap();
+        assert_eq!(hl2, hl);
+    }
+}
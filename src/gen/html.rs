@@ -1,12 +1,13 @@
 mod multi;
 mod single;
 
-use super::traverse_hotlist;
-use crate::ast::Hotlist;
+use super::{traverse_hotlist, TrashMode};
+use crate::ast::{decode_line_breaks, Hotlist, HotlistUrl};
 use crate::error::Error;
 use multi::MultiEmitter;
 use single::SingleEmitter;
 
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
 use std::path::Path;
@@ -15,10 +16,11 @@ pub fn emit<T: AsRef<Path>>(
     filename: Option<T>,
     hl: &Hotlist,
     multi: bool,
+    trash: TrashMode,
 ) -> Result<(), Error<'static>> {
     if multi {
         if let Some(fn_) = filename {
-            let mut emitter = MultiEmitter::new(fn_.as_ref());
+            let mut emitter = MultiEmitter::new(fn_.as_ref(), trash);
             traverse_hotlist(hl, &mut emitter)?;
         } else {
             // TODO: EmitError
@@ -32,26 +34,28 @@ pub fn emit<T: AsRef<Path>>(
             Box::new(BufWriter::new(io::stdout()))
         };
 
-        let mut emitter = SingleEmitter::new(out_handle);
-        traverse_hotlist(hl, &mut emitter)?;
-        let mut out_handle = emitter.into_inner();
-        out_handle.flush()?;
+        write(out_handle, hl, trash)?;
     }
 
     Ok(())
 }
 
+// Single-file mode only; multiple-file mode needs a directory to write into.
+pub fn write<W: Write>(out: W, hl: &Hotlist, trash: TrashMode) -> Result<(), Error<'static>> {
+    let mut emitter = SingleEmitter::new(out, trash);
+    traverse_hotlist(hl, &mut emitter)?;
+    let mut out = emitter.into_inner();
+    out.flush()?;
+
+    Ok(())
+}
+
 trait HtmlEscapeWrite: Write {
     fn write_with_escapes(&mut self, raw: &str) -> io::Result<()> {
-        let mut possible_newline = false;
-        for c in raw.chars() {
+        for c in decode_line_breaks(raw).chars() {
             match c {
-                '\x02' if !possible_newline => {
-                    possible_newline = true;
-                }
-                '\x02' if possible_newline => {
+                '\n' => {
                     write!(self, "</p>\n{:1$}<p>", " ", 4)?;
-                    possible_newline = false;
                 }
                 '<' => {
                     write!(self, "&lt;")?;
@@ -72,14 +76,6 @@ trait HtmlEscapeWrite: Write {
                     write!(self, "{}", c)?;
                 }
             }
-
-            // We're only interested in matching two \x02 chars back-to-back.
-            match c {
-                '\x02' => {}
-                _ => {
-                    possible_newline = false;
-                }
-            }
         }
 
         Ok(())
@@ -87,3 +83,106 @@ trait HtmlEscapeWrite: Write {
 }
 
 impl<W> HtmlEscapeWrite for W where W: Write {}
+
+// Fields the parser didn't recognize are passed through as-is, one list item each.
+fn write_extra_fields<W: Write>(
+    buf: &mut W,
+    extra: &[(Cow<str>, Cow<str>)],
+    indent: usize,
+) -> io::Result<()> {
+    for (k, v) in extra {
+        write!(buf, "{:1$}<li>", " ", indent)?;
+        buf.write_with_escapes(k)?;
+        write!(buf, ": ")?;
+        buf.write_with_escapes(v)?;
+        write!(buf, "</li>\n")?;
+    }
+
+    Ok(())
+}
+
+// Only URLs that parsed get a link; lenient mode may hand us raw text that could be anything.
+fn write_url_item<W: Write>(
+    buf: &mut W,
+    url: &Option<HotlistUrl>,
+    indent: usize,
+) -> io::Result<()> {
+    match url {
+        Some(HotlistUrl::Valid(u)) => {
+            write!(
+                buf,
+                "{:1$}<li>URL: <a href=\"{2}\">{2}</a></li>\n",
+                " ", indent, u
+            )
+        }
+        Some(HotlistUrl::Invalid(raw)) => {
+            write!(buf, "{:1$}<li>URL: ", " ", indent)?;
+            buf.write_with_escapes(raw)?;
+            write!(buf, "</li>\n")
+        }
+        None => write!(buf, "{:1$}<li>URL: None</li>\n", " ", indent),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write, HtmlEscapeWrite};
+    use crate::gen::TrashMode;
+    use crate::parser::{parse_str, ParseOptions};
+
+    #[test]
+    fn test_trash_mode() {
+        let inp = "Opera Hotlist version 2.0\n\
+        Options: encoding = utf8, version=3\n\
+        \n\
+        #FOLDER\n\
+        \tID=1\n\
+        \tNAME=Trash\n\
+        \tCREATED=0\n\
+        \tTRASH FOLDER=YES\n\
+        \tUNIQUEID=11111111111111111111111111111111\n\
+        \n\
+        #NOTE\n\
+        \tID=2\n\
+        \tUNIQUEID=22222222222222222222222222222222\n\
+        \tNAME=Deleted.\n\
+        \tCREATED=0\n\
+        \n\
+        -\n\
+        \n\
+        #NOTE\n\
+        \tID=3\n\
+        \tUNIQUEID=33333333333333333333333333333333\n\
+        \tNAME=Kept.\n\
+        \tCREATED=0\n";
+        let hl = parse_str(inp, &ParseOptions::default()).unwrap().hotlist;
+
+        let html = |trash| {
+            let mut out = Vec::new();
+            write(&mut out, &hl, trash).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let out = html(TrashMode::Include);
+        assert!(out.find("Deleted.").unwrap() < out.find("Kept.").unwrap());
+
+        let out = html(TrashMode::Exclude);
+        assert!(!out.contains("Deleted."));
+        assert!(out.contains("Kept."));
+
+        let out = html(TrashMode::Separate);
+        let trash = out.find("<h1>Trash</h1>").unwrap();
+        assert!(out.find("Kept.").unwrap() < trash);
+        assert!(trash < out.find("Deleted.").unwrap());
+    }
+
+    #[test]
+    fn test_escapes() {
+        let mut out = Vec::new();
+        out.write_with_escapes("<a & b>\x02\x02c\x02d").unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "&lt;a &amp; b&gt;</p>\n    <p>cd"
+        );
+    }
+}
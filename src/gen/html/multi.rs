@@ -1,7 +1,7 @@
-use super::HtmlEscapeWrite;
-use crate::ast::{Folder, Hotlist, Note};
+use super::{write_extra_fields, write_url_item, HtmlEscapeWrite};
+use crate::ast::{Bookmark, Contact, EntryKind, Folder, Hotlist, Note, Separator};
 use crate::error::Error;
-use crate::gen::Visitor;
+use crate::gen::{TrashDepth, TrashMode, Visitor};
 
 use std::fs::{create_dir_all, File, OpenOptions};
 use std::io::{BufWriter, Write};
@@ -9,14 +9,54 @@ use std::path::PathBuf;
 
 pub struct MultiEmitter {
     root: PathBuf,
+    trash: TrashMode,
+    trash_depth: TrashDepth,
+    // With TrashMode::Separate, the trash folder is written to its own directory next to the
+    // output, e.g. "notes-trash" for "notes". This is where to go back to afterwards.
+    saved_root: Option<PathBuf>,
 }
 
 impl MultiEmitter {
-    pub fn new<P>(root: P) -> Self
+    pub fn new<P>(root: P, trash: TrashMode) -> Self
     where
         P: Into<PathBuf>,
     {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            trash,
+            trash_depth: TrashDepth::default(),
+            saved_root: None,
+        }
+    }
+
+    fn excluded(&self) -> bool {
+        self.trash == TrashMode::Exclude && self.trash_depth.inside()
+    }
+
+    fn push_folder(&mut self, f: &Folder) {
+        if self.trash == TrashMode::Separate && f.trash && self.saved_root.is_none() {
+            let trash_root = match self.root.file_name() {
+                Some(name) => {
+                    let mut name = name.to_owned();
+                    name.push("-trash");
+                    self.root.with_file_name(name)
+                }
+                None => self.root.join("trash"),
+            };
+            self.saved_root = Some(std::mem::replace(&mut self.root, trash_root));
+        } else {
+            self.root.push(&*f.name);
+        }
+    }
+
+    fn pop_folder(&mut self, f: &Folder) {
+        match self.saved_root.take() {
+            Some(root) if f.trash => self.root = root,
+            saved_root => {
+                self.saved_root = saved_root;
+                self.root.pop();
+            }
+        }
     }
 
     fn write_note(&mut self, n: &Note) -> Result<(), Error<'static>> {
@@ -54,34 +94,190 @@ impl MultiEmitter {
             n.id, n.uuid
         )?;
 
-        // without "&": cannot move out of `n.url.0` which is behind a shared reference
-        if let Some(u) = &n.url {
+        write_url_item(&mut buf, &n.url, 6)?;
+
+        write!(
+            buf,
+            r#"      <li>Created: {}</li>
+"#,
+            n.timestamp
+        )?;
+        write_extra_fields(&mut buf, &n.extra, 6)?;
+        write!(buf, "    </ul>\n")?;
+
+        if let Some(nbody) = n.contents.as_deref() {
+            write!(buf, "    <p>")?;
+            buf.write_with_escapes(&nbody)?;
+            write!(buf, "<p>\n")?;
+        }
+
+        write!(
+            buf,
+            r#"  </body>
+</html>
+"#
+        )?;
+
+        buf.flush()?;
+        self.root.pop();
+        Ok(())
+    }
+
+    fn write_bookmark(&mut self, b: &Bookmark) -> Result<(), Error<'static>> {
+        self.root.push(b.id.to_string());
+        self.root.set_extension("html");
+
+        // IDs are shared between notes and bookmarks, so the same uniqueness check applies.
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.root)?;
+
+        let mut buf = BufWriter::new(file);
+
+        write!(
+            buf,
+            r#"<html>
+  <head>
+    <meta charset="utf-8">
+    <title>Opera Hotlist: Bookmark {}</title>
+  </head>
+  <body>
+"#,
+            b.id
+        )?;
+
+        write!(buf, "    <h1>Bookmark ")?;
+        buf.write_with_escapes(&b.name)?;
+        write!(
+            buf,
+            r#"</h1>
+    <ul>
+      <li>ID: {0}</li>
+      <li>UUID: {1}</li>
+"#,
+            b.id, b.uuid
+        )?;
+
+        write_url_item(&mut buf, &b.url, 6)?;
+
+        if let Some(sn) = b.short_name.as_deref() {
+            write!(buf, "      <li>Short Name: ")?;
+            buf.write_with_escapes(sn)?;
+            write!(buf, "</li>\n")?;
+        }
+
+        if let Some(icon) = b.icon_file.as_deref() {
+            write!(buf, "      <li>Icon File: ")?;
+            buf.write_with_escapes(icon)?;
+            write!(buf, "</li>\n")?;
+        }
+
+        write!(
+            buf,
+            r#"      <li>Created: {}</li>
+"#,
+            b.timestamp
+        )?;
+
+        if let Some(v) = b.visited {
             write!(
                 buf,
-                r#"      <li>URL: <a href="{0}">{0}</a></li>
+                r#"      <li>Visited: {}</li>
 "#,
-                u
+                v
             )?;
-        } else {
-            write!(
-                buf,
-                r#"      <li>URL: None</li>
+        }
+
+        write_extra_fields(&mut buf, &b.extra, 6)?;
+        write!(buf, "    </ul>\n")?;
+
+        if let Some(desc) = b.description.as_deref() {
+            write!(buf, "    <p>")?;
+            buf.write_with_escapes(desc)?;
+            write!(buf, "</p>\n")?;
+        }
+
+        write!(
+            buf,
+            r#"  </body>
+</html>
 "#
-            )?;
+        )?;
+
+        buf.flush()?;
+        self.root.pop();
+        Ok(())
+    }
+
+    fn write_contact(&mut self, c: &Contact) -> Result<(), Error<'static>> {
+        self.root.push(c.id.to_string());
+        self.root.set_extension("html");
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.root)?;
+
+        let mut buf = BufWriter::new(file);
+
+        write!(
+            buf,
+            r#"<html>
+  <head>
+    <meta charset="utf-8">
+    <title>Opera Hotlist: Contact {}</title>
+  </head>
+  <body>
+"#,
+            c.id
+        )?;
+
+        write!(buf, "    <h1>Contact ")?;
+        buf.write_with_escapes(&c.name)?;
+        write!(
+            buf,
+            r#"</h1>
+    <ul>
+      <li>ID: {0}</li>
+      <li>UUID: {1}</li>
+"#,
+            c.id, c.uuid
+        )?;
+
+        write_url_item(&mut buf, &c.url, 6)?;
+
+        let details = [
+            ("Short Name", c.short_name.as_deref()),
+            ("E-mail", c.mail.as_deref()),
+            ("Phone", c.phone.as_deref()),
+            ("Fax", c.fax.as_deref()),
+            ("Postal Address", c.postal_address.as_deref()),
+            ("Picture URL", c.picture_url.as_deref()),
+            ("Icon", c.icon.as_deref()),
+        ];
+
+        for (label, value) in details.iter() {
+            if let Some(v) = value {
+                write!(buf, "      <li>{}: ", label)?;
+                buf.write_with_escapes(v)?;
+                write!(buf, "</li>\n")?;
+            }
         }
 
         write!(
             buf,
             r#"      <li>Created: {}</li>
-    </ul>
 "#,
-            n.timestamp
+            c.timestamp
         )?;
+        write_extra_fields(&mut buf, &c.extra, 6)?;
+        write!(buf, "    </ul>\n")?;
 
-        if let Some(nbody) = n.contents {
+        if let Some(desc) = c.description.as_deref() {
             write!(buf, "    <p>")?;
-            buf.write_with_escapes(&nbody)?;
-            write!(buf, "<p>\n")?;
+            buf.write_with_escapes(desc)?;
+            write!(buf, "</p>\n")?;
         }
 
         write!(
@@ -119,6 +315,10 @@ Number of Entries: {}
             f.entries.len()
         )?;
 
+        for (k, v) in &f.extra {
+            write!(file, "{}: {}\n", k, v)?;
+        }
+
         self.root.pop();
         Ok(())
     }
@@ -126,13 +326,22 @@ Number of Entries: {}
     fn write_root_meta(&mut self, h: &Hotlist) -> Result<(), Error<'static>> {
         self.root.push("meta.txt");
 
+        let entries = match self.trash {
+            TrashMode::Include => h.entries.len(),
+            _ => h
+                .entries
+                .iter()
+                .filter(|e| !matches!(e, EntryKind::Folder(f) if f.trash))
+                .count(),
+        };
+
         let mut file = File::create(&self.root)?;
         write!(
             file,
             r"Hotlist Root
 Number of Entries: {}
 ",
-            h.entries.len()
+            entries
         )?;
 
         self.root.pop();
@@ -142,27 +351,49 @@ Number of Entries: {}
 
 impl<'ast, 'input> Visitor<'ast, 'input> for MultiEmitter {
     fn visit_folder_empty(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
-        self.root.push(f.name);
-        create_dir_all(&self.root)?;
-
-        self.write_folder_meta(f)?;
-
-        self.root.pop();
-        Ok(())
+        self.visit_folder_pre(f)?;
+        self.visit_folder_post(f)
     }
     fn visit_folder_pre(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
-        self.root.push(f.name);
+        self.trash_depth.enter(f);
+        if self.excluded() {
+            return Ok(());
+        }
+
+        self.push_folder(f);
         create_dir_all(&self.root)?;
         Ok(())
     }
     fn visit_folder_post(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
-        self.write_folder_meta(f)?;
+        if !self.excluded() {
+            self.write_folder_meta(f)?;
+            self.pop_folder(f);
+        }
 
-        self.root.pop();
+        self.trash_depth.leave();
         Ok(())
     }
     fn visit_note(&mut self, n: &'ast Note<'input>) -> Result<(), Error<'static>> {
-        self.write_note(n)?;
+        if !self.excluded() {
+            self.write_note(n)?;
+        }
+        Ok(())
+    }
+    fn visit_bookmark(&mut self, b: &'ast Bookmark<'input>) -> Result<(), Error<'static>> {
+        if !self.excluded() {
+            self.write_bookmark(b)?;
+        }
+        Ok(())
+    }
+    fn visit_contact(&mut self, c: &'ast Contact<'input>) -> Result<(), Error<'static>> {
+        if !self.excluded() {
+            self.write_contact(c)?;
+        }
+        Ok(())
+    }
+    fn visit_separator(&mut self, _s: &'ast Separator) -> Result<(), Error<'static>> {
+        // Entries become files in a directory, which has no ordering for a separator to
+        // break up.
         Ok(())
     }
     fn visit_root_pre(&mut self, _hotlist: &'ast Hotlist<'input>) -> Result<(), Error<'static>> {
@@ -1,27 +1,72 @@
-use super::HtmlEscapeWrite;
-use crate::ast::{Folder, Hotlist, Note};
+use super::{write_extra_fields, write_url_item, HtmlEscapeWrite};
+use crate::ast::{Bookmark, Contact, Folder, Hotlist, Note, Separator};
 use crate::error::Error;
-use crate::gen::Visitor;
+use crate::gen::{TrashDepth, TrashMode, Visitor};
 
-use std::io::Write;
+use std::io::{self, Write};
 
 pub struct SingleEmitter<W>
 where
     W: Write,
 {
-    buf: W,
+    buf: TrashWriter<W>,
+    trash_depth: TrashDepth,
+}
+
+// Output from inside the trash folder goes to out, nowhere, or is held back until the end of the
+// page, depending on the TrashMode.
+struct TrashWriter<W> {
+    out: W,
+    mode: TrashMode,
+    in_trash: bool,
+    trash: Vec<u8>,
+}
+
+impl<W> Write for TrashWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match (self.in_trash, self.mode) {
+            (false, _) | (true, TrashMode::Include) => self.out.write(buf),
+            (true, TrashMode::Exclude) => Ok(buf.len()),
+            (true, TrashMode::Separate) => self.trash.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
 }
 
 impl<W> SingleEmitter<W>
 where
     W: Write,
 {
-    pub fn new(buf: W) -> Self {
-        Self { buf }
+    pub fn new(buf: W, trash: TrashMode) -> Self {
+        Self {
+            buf: TrashWriter {
+                out: buf,
+                mode: trash,
+                in_trash: false,
+                trash: Vec::new(),
+            },
+            trash_depth: TrashDepth::default(),
+        }
     }
 
     pub fn into_inner(self) -> W {
-        self.buf
+        self.buf.out
+    }
+
+    fn enter_folder(&mut self, f: &Folder) {
+        self.trash_depth.enter(f);
+        self.buf.in_trash = self.trash_depth.inside();
+    }
+
+    fn leave_folder(&mut self) {
+        self.trash_depth.leave();
+        self.buf.in_trash = self.trash_depth.inside();
     }
 }
 
@@ -30,6 +75,7 @@ where
     W: Write,
 {
     fn visit_folder_empty(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
+        self.enter_folder(f);
         write!(self.buf, "{:1$}<h2>Folder {2}</h2>\n", " ", 4, f.name)?;
         write!(self.buf, "{:1$}<ul>\n", " ", 4)?;
         write!(self.buf, "{:1$}<li>ID: {2}</li>\n", " ", 6, f.id)?;
@@ -39,15 +85,18 @@ where
             "{:1$}<li>Created: {2}</li>\n",
             " ", 6, f.timestamp
         )?;
+        write_extra_fields(&mut self.buf, &f.extra, 6)?;
         write!(self.buf, "{:1$}</ul>\n", " ", 4)?;
 
         write!(self.buf, "{:1$}<p>No Entries<p>\n", " ", 4)?;
 
         write!(self.buf, "\n")?;
+        self.leave_folder();
         Ok(())
     }
 
     fn visit_folder_pre(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
+        self.enter_folder(f);
         write!(self.buf, "{:1$}<h2>Folder {2}</h2>\n", " ", 4, f.name)?;
         write!(self.buf, "{:1$}<ul>\n", " ", 4)?;
         write!(self.buf, "{:1$}<li>ID: {2}</li>\n", " ", 6, f.id)?;
@@ -57,6 +106,7 @@ where
             "{:1$}<li>Created: {2}</li>\n",
             " ", 6, f.timestamp
         )?;
+        write_extra_fields(&mut self.buf, &f.extra, 6)?;
         write!(self.buf, "{:1$}</ul>\n", " ", 4)?;
 
         write!(self.buf, "\n")?;
@@ -66,6 +116,7 @@ where
     fn visit_folder_post(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
         write!(self.buf, "{:1$}<p>End Folder {2}</p>\n", " ", 4, f.name)?;
         write!(self.buf, "\n")?;
+        self.leave_folder();
         Ok(())
     }
 
@@ -74,25 +125,17 @@ where
         write!(self.buf, "{:1$}<ul>\n", " ", 4)?;
         write!(self.buf, "{:1$}<li>UUID: {2}</li>\n", " ", 6, n.uuid)?;
 
-        // without "&": cannot move out of `n.url.0` which is behind a shared reference
-        if let Some(u) = &n.url {
-            write!(
-                self.buf,
-                "{:1$}<li>URL: <a href=\"{2}\">{2}</a></li>\n",
-                " ", 6, u
-            )?;
-        } else {
-            write!(self.buf, "{:1$}<li>URL: None</li>\n", " ", 6)?;
-        }
+        write_url_item(&mut self.buf, &n.url, 6)?;
 
         write!(
             self.buf,
             "{:1$}<li>Created: {2}</li>\n",
             " ", 6, n.timestamp
         )?;
+        write_extra_fields(&mut self.buf, &n.extra, 6)?;
         write!(self.buf, "{:1$}</ul>\n", " ", 4)?;
 
-        if let Some(nbody) = n.contents {
+        if let Some(nbody) = n.contents.as_deref() {
             write!(self.buf, "{:1$}<p>", " ", 4)?;
             self.buf.write_with_escapes(&nbody)?;
             write!(self.buf, "<p>\n")?;
@@ -102,6 +145,103 @@ where
         Ok(())
     }
 
+    fn visit_bookmark(&mut self, b: &'ast Bookmark<'input>) -> Result<(), Error<'static>> {
+        write!(self.buf, "{:1$}<h2>Bookmark ", " ", 4)?;
+        self.buf.write_with_escapes(&b.name)?;
+        write!(self.buf, "</h2>\n")?;
+        write!(self.buf, "{:1$}<ul>\n", " ", 4)?;
+        write!(self.buf, "{:1$}<li>ID: {2}</li>\n", " ", 6, b.id)?;
+        write!(self.buf, "{:1$}<li>UUID: {2}</li>\n", " ", 6, b.uuid)?;
+
+        write_url_item(&mut self.buf, &b.url, 6)?;
+
+        if let Some(sn) = b.short_name.as_deref() {
+            write!(self.buf, "{:1$}<li>Short Name: ", " ", 6)?;
+            self.buf.write_with_escapes(sn)?;
+            write!(self.buf, "</li>\n")?;
+        }
+
+        if let Some(icon) = b.icon_file.as_deref() {
+            write!(self.buf, "{:1$}<li>Icon File: ", " ", 6)?;
+            self.buf.write_with_escapes(icon)?;
+            write!(self.buf, "</li>\n")?;
+        }
+
+        write!(
+            self.buf,
+            "{:1$}<li>Created: {2}</li>\n",
+            " ", 6, b.timestamp
+        )?;
+
+        if let Some(v) = b.visited {
+            write!(self.buf, "{:1$}<li>Visited: {2}</li>\n", " ", 6, v)?;
+        }
+
+        write_extra_fields(&mut self.buf, &b.extra, 6)?;
+        write!(self.buf, "{:1$}</ul>\n", " ", 4)?;
+
+        if let Some(desc) = b.description.as_deref() {
+            write!(self.buf, "{:1$}<p>", " ", 4)?;
+            self.buf.write_with_escapes(desc)?;
+            write!(self.buf, "</p>\n")?;
+        }
+
+        write!(self.buf, "\n")?;
+        Ok(())
+    }
+
+    fn visit_contact(&mut self, c: &'ast Contact<'input>) -> Result<(), Error<'static>> {
+        write!(self.buf, "{:1$}<h2>Contact ", " ", 4)?;
+        self.buf.write_with_escapes(&c.name)?;
+        write!(self.buf, "</h2>\n")?;
+        write!(self.buf, "{:1$}<ul>\n", " ", 4)?;
+        write!(self.buf, "{:1$}<li>ID: {2}</li>\n", " ", 6, c.id)?;
+        write!(self.buf, "{:1$}<li>UUID: {2}</li>\n", " ", 6, c.uuid)?;
+
+        write_url_item(&mut self.buf, &c.url, 6)?;
+
+        let details = [
+            ("Short Name", c.short_name.as_deref()),
+            ("E-mail", c.mail.as_deref()),
+            ("Phone", c.phone.as_deref()),
+            ("Fax", c.fax.as_deref()),
+            ("Postal Address", c.postal_address.as_deref()),
+            ("Picture URL", c.picture_url.as_deref()),
+            ("Icon", c.icon.as_deref()),
+        ];
+
+        for (label, value) in details.iter() {
+            if let Some(v) = value {
+                write!(self.buf, "{:1$}<li>{2}: ", " ", 6, label)?;
+                self.buf.write_with_escapes(v)?;
+                write!(self.buf, "</li>\n")?;
+            }
+        }
+
+        write!(
+            self.buf,
+            "{:1$}<li>Created: {2}</li>\n",
+            " ", 6, c.timestamp
+        )?;
+        write_extra_fields(&mut self.buf, &c.extra, 6)?;
+        write!(self.buf, "{:1$}</ul>\n", " ", 4)?;
+
+        if let Some(desc) = c.description.as_deref() {
+            write!(self.buf, "{:1$}<p>", " ", 4)?;
+            self.buf.write_with_escapes(desc)?;
+            write!(self.buf, "</p>\n")?;
+        }
+
+        write!(self.buf, "\n")?;
+        Ok(())
+    }
+
+    fn visit_separator(&mut self, _s: &'ast Separator) -> Result<(), Error<'static>> {
+        write!(self.buf, "{:1$}<hr>\n", " ", 4)?;
+        write!(self.buf, "\n")?;
+        Ok(())
+    }
+
     fn visit_root_pre(&mut self, hl: &'ast Hotlist<'input>) -> Result<(), Error<'static>> {
         write!(
             self.buf,
@@ -124,6 +264,12 @@ where
     }
 
     fn visit_root_post(&mut self, _hl: &'ast Hotlist<'input>) -> Result<(), Error<'static>> {
+        if !self.buf.trash.is_empty() {
+            write!(self.buf, "{:1$}<h1>Trash</h1>\n", " ", 4)?;
+            let trash = std::mem::take(&mut self.buf.trash);
+            self.buf.write_all(&trash)?;
+        }
+
         write!(
             self.buf,
             r#"  </body>
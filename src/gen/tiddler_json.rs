@@ -1,7 +1,7 @@
 mod single;
 
-use super::traverse_hotlist;
-use crate::ast::Hotlist;
+use super::{traverse_hotlist, TrashMode};
+use crate::ast::{decode_line_breaks, Hotlist, HotlistUrl};
 use crate::error::Error;
 use single::SingleGenerator;
 
@@ -12,7 +12,6 @@ use std::path::{Path, PathBuf};
 
 use chrono::{self, Utc};
 use uuid;
-use url;
 
 use serde::{Serialize, Serializer};
 use serde_json::ser;
@@ -21,6 +20,7 @@ pub fn emit<T: AsRef<Path>>(
     filename: Option<T>,
     hl: &Hotlist,
     multi: bool,
+    trash: TrashMode,
 ) -> Result<(), Error<'static>> {
     if multi {
         // TODO: EmitError
@@ -33,13 +33,19 @@ pub fn emit<T: AsRef<Path>>(
             Box::new(BufWriter::new(io::stdout()))
         };
 
-        let mut gen = SingleGenerator::new();
-        traverse_hotlist(hl, &mut gen)?;
+        write(out_handle, hl, trash)?;
+    }
 
-        let mut serializer = serde_json::Serializer::pretty(out_handle);
+    Ok(())
+}
 
-        gen.serialize(&mut serializer)?;
-    }
+pub fn write<W: Write>(out: W, hl: &Hotlist, trash: TrashMode) -> Result<(), Error<'static>> {
+    let mut gen = SingleGenerator::new(trash);
+    traverse_hotlist(hl, &mut gen)?;
+
+    let mut serializer = serde_json::Serializer::pretty(out);
+
+    gen.serialize(&mut serializer)?;
 
     Ok(())
 }
@@ -88,16 +94,8 @@ struct NoteBody<'a>(&'a str);
 
 impl<'a> fmt::Display for NoteBody<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut possible_newline = false;
-        for c in self.0.chars() {
+        for c in decode_line_breaks(self.0).chars() {
             match c {
-                '\x02' if !possible_newline => {
-                    possible_newline = true;
-                }
-                '\x02' if possible_newline => {
-                    write!(f, "\n")?;
-                    possible_newline = false;
-                }
                 '<' => {
                     write!(f, "&lt;")?;
                 }
@@ -117,14 +115,6 @@ impl<'a> fmt::Display for NoteBody<'a> {
                     write!(f, "{}", c)?;
                 }
             }
-
-            // We're only interested in matching two \x02 chars back-to-back.
-            match c {
-                '\x02' => {}
-                _ => {
-                    possible_newline = false;
-                }
-            }
         }
 
         Ok(())
@@ -171,9 +161,9 @@ impl From<PathBuf> for Folder {
 }
 
 #[derive(Debug)]
-struct Url(Option<url::Url>);
+struct Url<'a>(Option<HotlistUrl<'a>>);
 
-impl<'a> Serialize for Url {
+impl<'a> Serialize for Url<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -185,26 +175,27 @@ impl<'a> Serialize for Url {
     }
 }
 
-impl From<Option<url::Url>> for Url {
-    fn from(url: Option<url::Url>) -> Self {
+impl<'a> From<Option<HotlistUrl<'a>>> for Url<'a> {
+    fn from(url: Option<HotlistUrl<'a>>) -> Self {
         Url(url)
     }
 }
 
+// Tiddler titles must be unique, so entries are titled by kind and ID rather than by name.
 #[derive(Debug)]
-struct Title(u32);
+struct Title(&'static str, u32);
 
 impl Serialize for Title {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.collect_str(&format_args!("Note {}", self.0))
+        serializer.collect_str(&format_args!("{} {}", self.0, self.1))
     }
 }
 
 impl From<u32> for Title {
     fn from(id: u32) -> Self {
-        Title(id)
+        Title("Note", id)
     }
 }
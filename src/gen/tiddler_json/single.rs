@@ -1,6 +1,6 @@
-use crate::ast::{Folder, Hotlist, Note};
+use crate::ast::{Bookmark, Contact, Folder, Hotlist, Note, Separator};
 use crate::error::Error;
-use crate::gen::Visitor;
+use crate::gen::{TrashDepth, TrashMode, Visitor};
 
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -16,7 +16,7 @@ enum SerializeType<'input> {
     NoteBody(super::NoteBody<'input>),
     Title(super::Title),
     U32(u32),
-    Url(super::Url),
+    Url(super::Url<'input>),
     Uuid(super::Uuid)
 }
 
@@ -42,6 +42,8 @@ pub struct SingleGenerator<'input> {
     json: Vec<HashMap<&'static str, SerializeType<'input>>>,
     root: PathBuf,
     now: DateTime<Utc>,
+    trash: TrashMode,
+    trash_depth: TrashDepth,
 }
 
 impl<'input> Serialize for SingleGenerator<'input> {
@@ -54,7 +56,7 @@ impl<'input> Serialize for SingleGenerator<'input> {
 }
 
 impl<'input> SingleGenerator<'input> {
-    pub fn new() -> Self {
+    pub fn new(trash: TrashMode) -> Self {
         let json = Vec::<HashMap<&'static str, SerializeType>>::new();
         let root = PathBuf::new();
         let now = Utc::now();
@@ -62,29 +64,50 @@ impl<'input> SingleGenerator<'input> {
             json,
             root,
             now,
+            trash,
+            trash_depth: TrashDepth::default(),
+        }
+    }
+
+    fn excluded(&self) -> bool {
+        self.trash == TrashMode::Exclude && self.trash_depth.inside()
+    }
+
+    // Trashed entries share one tag when kept separate, which keeps them off the landing page.
+    fn tags(&self, tags: &'static str) -> SerializeType<'input> {
+        if self.trash == TrashMode::Separate && self.trash_depth.inside() {
+            SerializeType::Str("opera-trash")
+        } else {
+            SerializeType::Str(tags)
         }
     }
 }
 
-impl<'ast, 'input> Visitor<'ast, 'input> for SingleGenerator<'input> {
+impl<'ast, 'input> Visitor<'ast, 'input> for SingleGenerator<'ast> {
     fn visit_folder_empty(&mut self, _f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
         Ok(())
     }
 
     fn visit_folder_pre(&mut self, f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
-        self.root.push(f.name);
+        self.trash_depth.enter(f);
+        self.root.push(&*f.name);
         Ok(())
     }
 
     fn visit_folder_post(&mut self, _f: &'ast Folder<'input>) -> Result<(), Error<'static>> {
+        self.trash_depth.leave();
         self.root.pop();
         Ok(())
     }
 
     fn visit_note(&mut self, n: &'ast Note<'input>) -> Result<(), Error<'static>> {
+        if self.excluded() {
+            return Ok(());
+        }
+
         let mut entry = HashMap::new();
 
-        entry.insert("text", SerializeType::NoteBody(n.contents.unwrap_or("").into()));
+        entry.insert("text", SerializeType::NoteBody(n.contents.as_deref().unwrap_or("").into()));
 
         // Hotlist-specific
         entry.insert("uuid", SerializeType::Uuid(n.uuid.into()));
@@ -93,7 +116,7 @@ impl<'ast, 'input> Visitor<'ast, 'input> for SingleGenerator<'input> {
 
         entry.insert("created", SerializeType::DateTime(self.now.into()));
         entry.insert("modified", SerializeType::DateTime(self.now.into()));
-        entry.insert("tags", SerializeType::Str("opera"));
+        entry.insert("tags", self.tags("opera"));
 
         // TODO: When building the landing page, show URL for each entry but truncate to
         // a reasonable number of characters.
@@ -111,6 +134,103 @@ impl<'ast, 'input> Visitor<'ast, 'input> for SingleGenerator<'input> {
         Ok(())
     }
 
+    fn visit_bookmark(&mut self, b: &'ast Bookmark<'input>) -> Result<(), Error<'static>> {
+        if self.excluded() {
+            return Ok(());
+        }
+
+        let mut entry = HashMap::new();
+
+        entry.insert("text", SerializeType::NoteBody(b.description.as_deref().unwrap_or("").into()));
+        entry.insert("caption", SerializeType::NoteBody((&*b.name).into()));
+
+        // Hotlist-specific
+        entry.insert("uuid", SerializeType::Uuid(b.uuid.into()));
+
+        entry.insert("timestamp", SerializeType::DateTime(b.timestamp.into()));
+
+        if let Some(v) = b.visited {
+            entry.insert("visited", SerializeType::DateTime(v.into()));
+        }
+
+        if let Some(sn) = b.short_name.as_deref() {
+            entry.insert("shortname", SerializeType::Str(sn));
+        }
+
+        if let Some(icon) = b.icon_file.as_deref() {
+            entry.insert("iconfile", SerializeType::Str(icon));
+        }
+
+        entry.insert("created", SerializeType::DateTime(self.now.into()));
+        entry.insert("modified", SerializeType::DateTime(self.now.into()));
+        entry.insert("tags", self.tags("opera-bookmark"));
+
+        entry.insert("title", SerializeType::Title(super::Title("Bookmark", b.id)));
+        entry.insert("url", SerializeType::Url(b.url.clone().into()));
+        entry.insert("id", SerializeType::U32(b.id));
+
+        entry.insert("folder", SerializeType::Folder(self.root.clone().into()));
+
+        entry.insert("commit-sha", SerializeType::Str(env!("VERGEN_GIT_SHA")));
+
+        self.json.push(entry);
+
+        Ok(())
+    }
+
+    fn visit_contact(&mut self, c: &'ast Contact<'input>) -> Result<(), Error<'static>> {
+        if self.excluded() {
+            return Ok(());
+        }
+
+        let mut entry = HashMap::new();
+
+        entry.insert("text", SerializeType::NoteBody(c.description.as_deref().unwrap_or("").into()));
+        entry.insert("caption", SerializeType::NoteBody((&*c.name).into()));
+
+        // Hotlist-specific
+        entry.insert("uuid", SerializeType::Uuid(c.uuid.into()));
+
+        entry.insert("timestamp", SerializeType::DateTime(c.timestamp.into()));
+
+        let details = [
+            ("shortname", c.short_name.as_deref()),
+            ("mail", c.mail.as_deref()),
+            ("phone", c.phone.as_deref()),
+            ("fax", c.fax.as_deref()),
+            ("postaladdress", c.postal_address.as_deref()),
+            ("pictureurl", c.picture_url.as_deref()),
+            ("icon", c.icon.as_deref()),
+        ];
+
+        for (field, value) in details.iter() {
+            if let Some(v) = value {
+                entry.insert(field, SerializeType::NoteBody((*v).into()));
+            }
+        }
+
+        entry.insert("created", SerializeType::DateTime(self.now.into()));
+        entry.insert("modified", SerializeType::DateTime(self.now.into()));
+        entry.insert("tags", self.tags("opera-contact"));
+
+        entry.insert("title", SerializeType::Title(super::Title("Contact", c.id)));
+        entry.insert("url", SerializeType::Url(c.url.clone().into()));
+        entry.insert("id", SerializeType::U32(c.id));
+
+        entry.insert("folder", SerializeType::Folder(self.root.clone().into()));
+
+        entry.insert("commit-sha", SerializeType::Str(env!("VERGEN_GIT_SHA")));
+
+        self.json.push(entry);
+
+        Ok(())
+    }
+
+    fn visit_separator(&mut self, _s: &'ast Separator) -> Result<(), Error<'static>> {
+        // Tiddlers are unordered; the landing page sorts them by ID instead.
+        Ok(())
+    }
+
     fn visit_root_pre(&mut self, _hl: &'ast Hotlist<'input>) -> Result<(), Error<'static>> {
         Ok(())
     }
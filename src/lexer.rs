@@ -20,6 +20,9 @@ pub enum Tok<'input> {
     Utf8,            // "utf8"
     Equal,           // "="
     NoteHeader,      // "#NOTE"
+    UrlHeader,       // "#URL"
+    ContactHeader,   // "#CONTACT"
+    SeparatorHeader, // "#SEPARATOR"
     Id,              // "ID"
     UniqueId,        // "UNIQUEID"
     Name,            // "NAME"
@@ -33,13 +36,27 @@ pub enum Tok<'input> {
     FolderHeader,    // "#FOLDER"
     FolderEnd,       // "-"
     Active,          // "ACTIVE"
+    Description,     // "DESCRIPTION"
+    ShortName,       // "SHORT NAME"
+    Visited,         // "VISITED"
+    IconFile,        // "ICONFILE"
+    Mail,            // "MAIL"
+    Phone,           // "PHONE"
+    Fax,             // "FAX"
+    PostalAddress,   // "POSTALADDRESS"
+    PictureUrl,      // "PICTUREURL"
+    Icon,            // "ICON"
 
     // Regex-based
     Version(&'input str),
     Integer(&'input str),
     Uuid(&'input str),
+    Key(&'input str),
     UrlBody(&'input str),
     NoteBody(&'input str),
+
+    // A line RecoveringLexer couldn't read, up to the newline.
+    Invalid,
 }
 
 impl<'input> fmt::Display for Tok<'input> {
@@ -52,6 +69,9 @@ impl<'input> fmt::Display for Tok<'input> {
             Tok::Utf8 => write!(f, r#"UTF8 ("utf8")"#),
             Tok::Equal => write!(f, r#"Equals sign ("=")"#),
             Tok::NoteHeader => write!(f, r##"Note header ("#NOTE")"##),
+            Tok::UrlHeader => write!(f, r##"Bookmark header ("#URL")"##),
+            Tok::ContactHeader => write!(f, r##"Contact header ("#CONTACT")"##),
+            Tok::SeparatorHeader => write!(f, r##"Separator header ("#SEPARATOR")"##),
             Tok::Id => write!(f, r#"Numeric ID field ("ID")"#),
             Tok::UniqueId => write!(f, r#"UUID field ("UNIQUEID")"#),
             Tok::Name => write!(f, r#"Note body field ("NAME")"#),
@@ -65,11 +85,22 @@ impl<'input> fmt::Display for Tok<'input> {
             Tok::FolderHeader => write!(f, r##"Folder header ("#FOLDER")"##),
             Tok::FolderEnd => write!(f, r#"End of folder delimiter ("-")"#),
             Tok::Active => write!(f, r#"Active ("ACTIVE")"#),
+            Tok::Description => write!(f, r#"Description field ("DESCRIPTION")"#),
+            Tok::ShortName => write!(f, r#"Short name field ("SHORT NAME")"#),
+            Tok::Visited => write!(f, r#"Last visited field ("VISITED")"#),
+            Tok::IconFile => write!(f, r#"Icon file field ("ICONFILE")"#),
+            Tok::Mail => write!(f, r#"E-mail field ("MAIL")"#),
+            Tok::Phone => write!(f, r#"Phone field ("PHONE")"#),
+            Tok::Fax => write!(f, r#"Fax field ("FAX")"#),
+            Tok::PostalAddress => write!(f, r#"Postal address field ("POSTALADDRESS")"#),
+            Tok::PictureUrl => write!(f, r#"Picture URL field ("PICTUREURL")"#),
+            Tok::Icon => write!(f, r#"Icon field ("ICON")"#),
 
             // Regex-based
             Tok::Version(ver) => write!(f, r#"Version ("{}")"#, ver),
             Tok::Integer(int) => write!(f, r#"Integer ("{}")"#, int),
             Tok::Uuid(uuid) => write!(f, r#"UUID ("{}")"#, uuid),
+            Tok::Key(key) => write!(f, r#"Field name ("{}")"#, key),
             Tok::UrlBody(url) => write!(f, r#"URL ("{}")"#, url),
             Tok::NoteBody(note) => {
                 if note.len() < 80 {
@@ -78,13 +109,19 @@ impl<'input> fmt::Display for Tok<'input> {
                     write!(f, r#"Note body ("{}"... [cont])"#, note)
                 }
             }
+
+            Tok::Invalid => write!(f, "unrecognized input"),
         }
     }
 }
 
 #[derive(Default)]
 pub struct LexerState {
-    in_note_name: bool,
+    // Set by fields whose value is free text (NAME, DESCRIPTION, etc.), so that the next "="
+    // slurps up the rest of the line.
+    in_text_value: bool,
+    // Same, but for URL. Any scheme is allowed, so the rest of the line is the URL.
+    in_url_value: bool,
 }
 
 lexer! {
@@ -92,12 +129,14 @@ lexer! {
     type Error<'input> = ast::HotlistError<'input>;
 
     let version_re = ['0'-'9']+ ('.'['0'-'9'])? ['0'-'9']*;
-    let integer_re = ['0'-'9']*;
+    // Timestamps can be negative. A lone "-" is still the end of a folder.
+    let integer_re = '-'? ['0'-'9']+;
     let uuid_re = ['0'-'9' 'A'-'F']*;
-    // Avoid conflicts with literals like "utf8," by only parsing a subset of valid URLs.
-    let url_re = ("http" | "https") ($$ascii_alphanumeric | ":" | "/" | "." | "-" | "~" | "_" | "#" | "$" | "," |
-        ";" | "(" | ")" | "'" | "?" | "[" | "]" | "@" | "!" | "&" | "*" | "+" | "=" |
-        ("%" ['0'-'9' 'A'-'F'] ['0'-'9' 'A'-'F']))+;
+    // Field names we don't have a literal for. Multi-word names like "ON PERSONALBAR" only
+    // have spaces between words, so trailing whitespace after "YES"/"NO" is still skipped.
+    // Words can have digits after the first letter, e.g. "M2INDEXID" in contacts.
+    let key_word_re = ['A'-'Z' '_'] ['A'-'Z' '0'-'9' '_']*;
+    let key_re = $key_word_re (' ' $key_word_re)*;
 
     // Rule for everything except slurping up note body and URLs.
     rule Init {
@@ -111,23 +150,86 @@ lexer! {
         "utf8" = Tok::Utf8,
 
         "=" => |mut lexer| {
-            if lexer.state().in_note_name {
+            let in_value = lexer.state().in_text_value || lexer.state().in_url_value;
+            let empty = matches!(lexer.peek(), Some('\n') | Some('\r') | None);
+
+            if in_value && empty {
+                // Empty value- there is no body to slurp, and the parser treats a missing
+                // body as "" (or no URL).
+                lexer.state().in_text_value = false;
+                lexer.state().in_url_value = false;
+                lexer.return_(Tok::Equal)
+            } else if lexer.state().in_text_value {
                 lexer.switch_and_return(LexerRule::NoteBody, Tok::Equal)
+            } else if lexer.state().in_url_value {
+                lexer.switch_and_return(LexerRule::UrlBody, Tok::Equal)
             } else {
                 lexer.return_(Tok::Equal)
             }
         },
 
         "#NOTE" = Tok::NoteHeader,
+        "#URL" = Tok::UrlHeader,
+        "#CONTACT" = Tok::ContactHeader,
+        "#SEPARATOR" = Tok::SeparatorHeader,
         "ID" = Tok::Id,
         "UNIQUEID" = Tok::UniqueId,
 
         "NAME" => |mut lexer| {
-             lexer.state().in_note_name = true;
+             lexer.state().in_text_value = true;
              lexer.return_(Tok::Name)
         },
 
-        "URL" = Tok::Url,
+        "DESCRIPTION" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::Description)
+        },
+
+        "SHORT NAME" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::ShortName)
+        },
+
+        "ICONFILE" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::IconFile)
+        },
+
+        "MAIL" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::Mail)
+        },
+
+        "PHONE" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::Phone)
+        },
+
+        "FAX" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::Fax)
+        },
+
+        "POSTALADDRESS" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::PostalAddress)
+        },
+
+        "PICTUREURL" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::PictureUrl)
+        },
+
+        "ICON" => |mut lexer| {
+             lexer.state().in_text_value = true;
+             lexer.return_(Tok::Icon)
+        },
+
+        "URL" => |mut lexer| {
+             lexer.state().in_url_value = true;
+             lexer.return_(Tok::Url)
+        },
+
         "CREATED" = Tok::Created,
         "," = Tok::Comma,
         "EXPANDED" = Tok::Expanded,
@@ -137,6 +239,7 @@ lexer! {
         "#FOLDER" = Tok::FolderHeader,
         "-" = Tok::FolderEnd,
         "ACTIVE" = Tok::Active,
+        "VISITED" = Tok::Visited,
 
         // Regexes
         $integer_re => |lexer| {
@@ -149,14 +252,24 @@ lexer! {
             lexer.return_(Tok::Version(match_))
         },
 
-        $uuid_re => |lexer| {
+        $uuid_re => |mut lexer| {
             let match_ = lexer.match_();
-            lexer.return_(Tok::Uuid(match_))
+
+            // An unknown field name made only of the letters A-F is indistinguishable from a
+            // UUID, except that a field name is immediately followed by "=".
+            if lexer.peek() == Some('=') {
+                lexer.state().in_text_value = true;
+                lexer.return_(Tok::Key(match_))
+            } else {
+                lexer.return_(Tok::Uuid(match_))
+            }
         },
 
-        $url_re => |lexer| {
+        // Literal field names above win ties with this.
+        $key_re => |mut lexer| {
             let match_ = lexer.match_();
-            lexer.return_(Tok::UrlBody(match_))
+            lexer.state().in_text_value = true;
+            lexer.return_(Tok::Key(match_))
         },
     }
 
@@ -167,15 +280,29 @@ lexer! {
                 // FIXME: Explicitly handle carriage return not followed by newline?
                 Some('\n') | Some('\r') => {
                     let match_ = lexer.match_();
-                    lexer.state().in_note_name = false;
+                    lexer.state().in_text_value = false;
 
-                    // TODO: Handle empty slice.
                     lexer.switch_and_return(LexerRule::Init, Tok::NoteBody(&match_))
                 },
                 _ => lexer.continue_()
             }
         },
     }
+
+    // Same as NoteBody; the parser checks that the URL is actually valid.
+    rule UrlBody {
+        _ => |mut lexer| {
+            match lexer.peek() {
+                Some('\n') | Some('\r') => {
+                    let match_ = lexer.match_();
+                    lexer.state().in_url_value = false;
+
+                    lexer.switch_and_return(LexerRule::Init, Tok::UrlBody(&match_))
+                },
+                _ => lexer.continue_()
+            }
+        },
+    }
 }
 
 impl<'input> fmt::Display for LexerError<'input> {
@@ -197,3 +324,45 @@ impl error::Error for LexerError<'static> {
         }
     }
 }
+
+// Turns a LexerError into a Tok::Invalid covering the rest of the line, then carries on lexing
+// from the next line. That way the parser can skip just the entry with the bad line, as it does
+// for other syntax errors.
+pub struct RecoveringLexer<'input> {
+    input: &'input str,
+    // Where the current lexer started in input; its offsets are relative to that.
+    offset: usize,
+    lexer: Lexer<'input>,
+}
+
+impl<'input> RecoveringLexer<'input> {
+    pub fn new(input: &'input str) -> Self {
+        Self {
+            input,
+            offset: 0,
+            lexer: Lexer::new(input),
+        }
+    }
+}
+
+impl<'input> Iterator for RecoveringLexer<'input> {
+    type Item = Result<(usize, Tok<'input>, usize), LexerError<'input>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lexer.next()? {
+            Ok((lo, tok, hi)) => Some(Ok((lo + self.offset, tok, hi + self.offset))),
+            Err(LexerError::LexerError { char_idx }) => {
+                let lo = self.offset + char_idx;
+                let hi = self.input[lo..]
+                    .find(['\n', '\r'])
+                    .map_or(self.input.len(), |n| lo + n);
+
+                // A fresh lexer also resets LexerState, which only ever spans one line.
+                self.offset = hi;
+                self.lexer = Lexer::new(&self.input[hi..]);
+                Some(Ok((lo, Tok::Invalid, hi)))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+// Parsing and conversion of Opera Hotlist (.adr) files. The hl2html binary is a thin wrapper
+// around this.
+
+pub mod ast;
+pub mod check;
+pub mod cst;
+pub mod diff;
+pub mod edit;
+pub mod error;
+pub mod gen;
+pub mod lexer;
+pub mod merge;
+pub mod parser;
+pub mod search;
+pub mod select;
+pub mod stats;
+#[cfg(test)]
+mod test_support;
+
+pub use parser::{parse_hotlist_from_file, parse_reader, parse_str, ParseOptions, ParsedHotlist};
@@ -1,35 +1,96 @@
-mod ast;
-mod error;
-mod gen;
-mod lexer;
-mod parser;
-
 use argh::FromArgs;
+use hl2html::error::Diagnostic;
+use hl2html::{ast, check, diff, error, gen, merge, parser, search, select, stats};
+
+use serde_json::json;
+use std::io::{self, IsTerminal};
+
+// Every subcommand reads hotlists the same way. argh can't flatten a shared struct into each
+// one, so this adds the input options to a subcommand's struct, keeping their help in one place.
+macro_rules! input_args {
+    ($(#[$attr:meta])* struct $name:ident { $($fields:tt)* }) => {
+        $(#[$attr])*
+        struct $name {
+            $($fields)*
+            /// input encoding, e.g. utf-8 or windows-1252 (default detected from the header)
+            #[argh(option, short = 'e', from_str_fn(encoding_label))]
+            encoding: Option<&'static encoding_rs::Encoding>,
+            /// keep going past invalid values, missing fields and broken entries, with a warning,
+            /// and use what's left
+            #[argh(switch, short = 'l')]
+            lenient: bool,
+            /// print errors as human-readable text or one JSON object per line (human, json)
+            #[argh(option, default = "MessageFormat::Human", from_str_fn(message_format))]
+            message_format: MessageFormat,
+        }
+
+        impl $name {
+            fn parse_options(&self) -> parser::ParseOptions {
+                parser::ParseOptions {
+                    encoding: self.encoding,
+                    lenient: self.lenient,
+                }
+            }
+        }
+    };
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Convert, check and compare Opera Hotlist files. Without a command, hl2html converts.
+struct TopLevel {
+    #[argh(subcommand)]
+    command: Command,
+}
 
 #[derive(FromArgs, PartialEq, Debug)]
-/// Convert Opera Hotlist files to another format
-struct HotlistArgs {
-    /// create multiple files, index is main file.
-    #[argh(switch, short = 'm')]
-    multiple: bool,
-    /// emit hotlist in the selected mode (default HTML)
-    #[argh(
-        option,
-        short = 'f',
-        default = "default_format()",
-        from_str_fn(output_format)
-    )]
-    format: OutputFormat,
-    /// output file or directory (if multiple files)
-    #[argh(option, short = 'o')]
-    output: Option<String>,
-    /// input .adr file
-    #[argh(positional)]
-    path: String,
+#[argh(subcommand)]
+enum Command {
+    Convert(ConvertArgs),
+    Check(CheckArgs),
+    Stats(StatsArgs),
+    Search(SearchArgs),
+    Diff(DiffArgs),
+    Merge(MergeArgs),
+    Dump(DumpArgs),
+}
+
+input_args! {
+    #[derive(FromArgs, PartialEq, Debug)]
+    /// Convert Opera Hotlist files to another format
+    #[argh(subcommand, name = "convert")]
+    struct ConvertArgs {
+        /// create multiple files, index is main file.
+        #[argh(switch, short = 'm')]
+        multiple: bool,
+        /// emit hotlist in the selected mode (default HTML)
+        #[argh(
+            option,
+            short = 'f',
+            default = "default_format()",
+            from_str_fn(output_format)
+        )]
+        format: OutputFormat,
+        /// output file or directory (if multiple files)
+        #[argh(option, short = 'o')]
+        output: Option<String>,
+        /// only convert the entries that match a query, and the folders they're in, e.g.
+        /// 'folder:"Work/*" and created>=2009-01-01 and url~github.com'
+        #[argh(option)]
+        select: Option<String>,
+        /// what to do with the trash folder in HTML and Tiddler JSON output: keep it with
+        /// everything else, leave it out, or put it in its own section, directory or tag (include,
+        /// exclude, separate; default include)
+        #[argh(option, default = "gen::TrashMode::Include", from_str_fn(trash_mode))]
+        trash: gen::TrashMode,
+        /// input .adr file
+        #[argh(positional)]
+        path: String,
+    }
 }
 
 #[derive(PartialEq, Debug)]
 enum OutputFormat {
+    Adr,
     Html,
     Markdown,
     TiddlerJson,
@@ -38,12 +99,13 @@ enum OutputFormat {
 
 fn output_format(f: &str) -> Result<OutputFormat, String> {
     match f {
+        "adr" => Ok(OutputFormat::Adr),
         "html" => Ok(OutputFormat::Html),
         "markdown" => Ok(OutputFormat::Markdown),
         "tiddlerjson" => Ok(OutputFormat::TiddlerJson),
         "wikitext" => Ok(OutputFormat::WikiText),
         _ => Err(String::from(
-            "unknown output format (html, markdown, tiddlerjson, wikitext)",
+            "unknown output format (adr, html, markdown, tiddlerjson, wikitext)",
         )),
     }
 }
@@ -52,61 +114,713 @@ fn default_format() -> OutputFormat {
     OutputFormat::Html
 }
 
+input_args! {
+    #[derive(FromArgs, PartialEq, Debug)]
+    /// Merge hotlists by UUID into one .adr file
+    #[argh(subcommand, name = "merge")]
+    struct MergeArgs {
+        /// output file (default stdout)
+        #[argh(option, short = 'o')]
+        output: Option<String>,
+        /// when entries with the same UUID differ, keep the newest, keep both or fail (newest,
+        /// keep-both, fail; default newest)
+        #[argh(
+            option,
+            default = "merge::ConflictPolicy::Newest",
+            from_str_fn(conflict_policy)
+        )]
+        on_conflict: merge::ConflictPolicy,
+        /// input .adr files, at least two; the first one's entries come first
+        #[argh(positional)]
+        paths: Vec<String>,
+    }
+}
+
+input_args! {
+    #[derive(FromArgs, PartialEq, Debug)]
+    /// Compare two hotlists by UUID and list added, removed, moved and modified entries
+    #[argh(subcommand, name = "diff")]
+    struct DiffArgs {
+        /// print changes as human-readable text or one JSON object per line (human, json)
+        #[argh(option, default = "ReportFormat::Human", from_str_fn(report_format))]
+        format: ReportFormat,
+        /// the older .adr file
+        #[argh(positional)]
+        old: String,
+        /// the newer .adr file
+        #[argh(positional)]
+        new: String,
+    }
+}
+
+input_args! {
+    #[derive(FromArgs, PartialEq, Debug)]
+    /// Search note contents, folder names and URLs
+    #[argh(subcommand, name = "search")]
+    struct SearchArgs {
+        /// treat the pattern as a regular expression rather than plain text
+        #[argh(switch, short = 'r')]
+        regex: bool,
+        /// ignore case
+        #[argh(switch, short = 'i')]
+        ignore_case: bool,
+        /// highlight matches with terminal colors: when stdout is a terminal, always or never
+        /// (auto, always, never; default auto)
+        #[argh(option, default = "Color::Auto", from_str_fn(color))]
+        color: Color,
+        /// input .adr file
+        #[argh(positional)]
+        path: String,
+        /// text to search for
+        #[argh(positional)]
+        pattern: String,
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+fn color(c: &str) -> Result<Color, String> {
+    match c {
+        "auto" => Ok(Color::Auto),
+        "always" => Ok(Color::Always),
+        "never" => Ok(Color::Never),
+        _ => Err(String::from("unknown color choice (auto, always, never)")),
+    }
+}
+
+input_args! {
+    #[derive(FromArgs, PartialEq, Debug)]
+    /// Summarize what's in a hotlist
+    #[argh(subcommand, name = "stats")]
+    struct StatsArgs {
+        /// print the report as human-readable text or JSON (human, json)
+        #[argh(option, default = "ReportFormat::Human", from_str_fn(report_format))]
+        format: ReportFormat,
+        /// how many of the most used URL hosts to list (default 10)
+        #[argh(option, default = "10")]
+        top_hosts: usize,
+        /// input .adr file
+        #[argh(positional)]
+        path: String,
+    }
+}
+
+input_args! {
+    #[derive(FromArgs, PartialEq, Debug)]
+    /// Check a hotlist for problems the parser doesn't catch, and exit with the code of the first
+    /// rule that found an error
+    #[argh(subcommand, name = "check")]
+    struct CheckArgs {
+        /// print findings as human-readable text or one JSON object per line (human, json)
+        #[argh(option, default = "ReportFormat::Human", from_str_fn(report_format))]
+        format: ReportFormat,
+        /// change how a rule is reported, e.g. empty-note=error (allow, warning, error); the rules
+        /// are duplicate-id, duplicate-uuid and multiple-trash (errors by default), and
+        /// duplicate-folder-name, future-timestamp, zero-timestamp, empty-note and multiple-active
+        /// (warnings by default)
+        #[argh(option, from_str_fn(rule_severity))]
+        rule: Vec<(check::Rule, check::Severity)>,
+        /// input .adr file
+        #[argh(positional)]
+        path: String,
+    }
+}
+
+input_args! {
+    #[derive(FromArgs, PartialEq, Debug)]
+    /// Print the parsed hotlist, for seeing what the parser made of a file
+    #[argh(subcommand, name = "dump")]
+    struct DumpArgs {
+        /// print the tree as JSON rather than Rust's debug format (needs the serialize feature)
+        #[argh(switch)]
+        json: bool,
+        /// input .adr file
+        #[argh(positional)]
+        path: String,
+    }
+}
+
+fn message_format(f: &str) -> Result<MessageFormat, String> {
+    match f {
+        "human" => Ok(MessageFormat::Human),
+        "json" => Ok(MessageFormat::Json),
+        _ => Err(String::from("unknown message format (human, json)")),
+    }
+}
+
+// What diff, stats and check print on stdout. Errors go through --message-format instead.
+#[derive(PartialEq, Debug)]
+enum ReportFormat {
+    Human,
+    Json,
+}
+
+fn report_format(f: &str) -> Result<ReportFormat, String> {
+    match f {
+        "human" => Ok(ReportFormat::Human),
+        "json" => Ok(ReportFormat::Json),
+        _ => Err(String::from("unknown report format (human, json)")),
+    }
+}
+
+fn conflict_policy(p: &str) -> Result<merge::ConflictPolicy, String> {
+    match p {
+        "newest" => Ok(merge::ConflictPolicy::Newest),
+        "keep-both" => Ok(merge::ConflictPolicy::KeepBoth),
+        "fail" => Ok(merge::ConflictPolicy::Fail),
+        _ => Err(String::from("unknown conflict policy (newest, keep-both, fail)")),
+    }
+}
+
+fn rule_severity(r: &str) -> Result<(check::Rule, check::Severity), String> {
+    let (rule, severity) = match r.find('=') {
+        Some(i) => (&r[..i], &r[i + 1..]),
+        None => return Err(String::from("expected rule=severity")),
+    };
+    let rule = check::Rule::from_name(rule).ok_or_else(|| format!("unknown rule {}", rule))?;
+    let severity = check::Severity::from_name(severity)
+        .ok_or_else(|| String::from("unknown severity (allow, warning, error)"))?;
+    Ok((rule, severity))
+}
+
+fn trash_mode(t: &str) -> Result<gen::TrashMode, String> {
+    match t {
+        "include" => Ok(gen::TrashMode::Include),
+        "exclude" => Ok(gen::TrashMode::Exclude),
+        "separate" => Ok(gen::TrashMode::Separate),
+        _ => Err(String::from("unknown trash mode (include, exclude, separate)")),
+    }
+}
+
+fn encoding_label(e: &str) -> Result<&'static encoding_rs::Encoding, String> {
+    encoding_rs::Encoding::for_label(e.as_bytes())
+        .ok_or_else(|| format!("unknown encoding {}", e))
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum MessageFormat {
+    Human,
+    // One JSON object per line on stderr, for tools. Stdout is left to the output proper.
+    Json,
+}
+
+// Prints errors and warnings about one hotlist file, or about no file in particular.
+struct Reporter<'a> {
+    file: Option<&'a str>,
+    format: MessageFormat,
+    // The decoded hotlist, once it has been read. Parser offsets are into the decoded text rather
+    // than the file on disk, which differ for legacy encodings.
+    lines: Option<parser::LineIndex<'a>>,
+}
+
+impl<'a> Reporter<'a> {
+    fn new(file: &'a str, format: MessageFormat) -> Self {
+        Self {
+            file: Some(file),
+            format,
+            lines: None,
+        }
+    }
+
+    fn without_file(format: MessageFormat) -> Self {
+        Self {
+            file: None,
+            format,
+            lines: None,
+        }
+    }
+
+    fn print_error_and_exit<'e>(&self, context: &str, err: error::Error<'e>, exit_code: i32) -> ! {
+        // Safety:
+        // * Only place we want to downcast in the codebase.
+        // * There is a single lifetime 'input tied to in_buf.
+        // * "err"'s lifetime is tied to in_buf.
+        // * Since we are using "err" at this point before any unsafe code, the lifetime must still
+        //   be valid.
+        // * No references escape this function- so we can't type pun non-static lifetimes as
+        //   static lifetimes.
+        let mut curr_err: &(dyn std::error::Error + 'static) = &*unsafe {
+            std::mem::transmute::<
+                &(dyn std::error::Error + Send + Sync + 'e),
+                &(dyn std::error::Error + Send + Sync + 'static),
+            >(&*err)
+        };
+
+        let mut chain = Vec::new();
+        let mut diag = None;
+        loop {
+            // Show location information about the error if possible.
+            if let (Some(lines), Some(p_err)) = (
+                &self.lines,
+                curr_err.downcast_ref::<parser::HotlistParseError<'static>>(),
+            ) {
+                diag = Some(Diagnostic::new(p_err, lines));
+                break;
+            }
+
+            chain.push(curr_err.to_string());
+            match curr_err.source() {
+                Some(new_err) => curr_err = new_err,
+                None => break,
+            }
+        }
+
+        match self.format {
+            MessageFormat::Human => {
+                eprintln!("{}:", context);
+                for msg in &chain {
+                    eprintln!("{}", msg);
+                }
+                if let Some(diag) = &diag {
+                    self.print_diagnostic(diag);
+                }
+            }
+            MessageFormat::Json => {
+                let diag = match diag {
+                    Some(diag) => {
+                        chain.push(diag.message.clone());
+                        Diagnostic {
+                            message: chain.join(": "),
+                            ..diag
+                        }
+                    }
+                    None => {
+                        let kind = if curr_err.is::<io::Error>() {
+                            "Io"
+                        } else {
+                            "Other"
+                        };
+                        Diagnostic::other(kind, chain.join(": "))
+                    }
+                };
+                self.print_json(&diag, "error", Some(context));
+            }
+        }
+
+        std::process::exit(exit_code);
+    }
+
+    // Errors from entries the parser skipped over. These don't end the parse, so the caller
+    // decides whether to carry on.
+    fn print_parse_errors(&self, errors: &[parser::HotlistParseError]) {
+        if self.format == MessageFormat::Human {
+            eprintln!("Errors while parsing hotlist file:");
+        }
+
+        for p_err in errors {
+            // Without the text there's no location to show, but the error itself still matters.
+            let diag = match &self.lines {
+                Some(lines) => Diagnostic::new(p_err, lines),
+                None => Diagnostic::other("Other", p_err.to_string()),
+            };
+            match self.format {
+                MessageFormat::Human => self.print_diagnostic(&diag),
+                MessageFormat::Json => self.print_json(&diag, "error", None),
+            }
+        }
+    }
+
+    fn print_warnings(&self, warnings: &[ast::HotlistWarning]) {
+        for w in warnings {
+            match (self.format, &self.lines) {
+                (MessageFormat::Json, Some(lines)) => {
+                    let diag = Diagnostic {
+                        message: w.to_string(),
+                        ..Diagnostic::from_hotlist_error(&w.error, lines)
+                    };
+                    self.print_json(&diag, "warning", None);
+                }
+                _ => eprintln!("warning: {}", w),
+            }
+        }
+    }
+
+    fn print_diagnostic(&self, diag: &Diagnostic) {
+        eprintln!("error: {}", diag.message);
+
+        let mut gutter = 0;
+        if let (Some((start, end)), Some(lines), Some(file)) =
+            (diag.location, &self.lines, self.file)
+        {
+            let line = lines.line(start.line);
+            gutter = start.line.to_string().len();
+
+            // Reuse the line's tabs so the carets line up however wide a tab is shown.
+            let pad: String = line
+                .chars()
+                .take(start.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = if end.line == start.line {
+                end.column - start.column
+            } else {
+                line.chars().count() + 1 - start.column
+            };
+
+            eprintln!(
+                "{:w$}--> {}:{}:{}",
+                "",
+                file,
+                start.line,
+                start.column,
+                w = gutter
+            );
+            eprintln!("{:w$} |", "", w = gutter);
+            eprintln!("{} | {}", start.line, line);
+            eprintln!(
+                "{:w$} | {}{}",
+                "",
+                pad,
+                "^".repeat(width.max(1)),
+                w = gutter
+            );
+        }
+
+        if !diag.expected.is_empty() {
+            eprintln!(
+                "{:w$} = expected one of: {}",
+                "",
+                diag.expected.join(", "),
+                w = gutter
+            );
+        }
+
+        eprintln!();
+    }
+
+    fn print_json(&self, diag: &Diagnostic, level: &str, context: Option<&str>) {
+        let (line, column) = match diag.location {
+            Some((start, _)) => (Some(start.line), Some(start.column)),
+            None => (None, None),
+        };
+
+        let obj = json!({
+            "level": level,
+            "kind": diag.kind,
+            "message": diag.message,
+            "context": context,
+            "file": self.file,
+            "span": diag.span.map(|(start, end)| json!({ "start": start, "end": end })),
+            "line": line,
+            "column": column,
+            "expected": diag.expected,
+        });
+        eprintln!("{}", obj);
+    }
+}
+
+// Reads and parses one hotlist, printing any warnings and errors, and exits unless there's a
+// hotlist to work with.
+fn load_hotlist<'a>(
+    path: &str,
+    in_buf: &'a mut String,
+    opts: &parser::ParseOptions,
+    format: MessageFormat,
+) -> ast::Hotlist<'a> {
+    let mut reporter = Reporter::new(path, format);
+
+    let encoding = parser::read_hotlist_file(path, in_buf, opts).unwrap_or_else(|e| {
+        reporter.print_error_and_exit("Error while reading hotlist file", e, 1);
+    });
+    let in_buf: &'a String = in_buf;
+    reporter.lines = Some(parser::LineIndex::new(in_buf));
+
+    let parsed = parser::parse_hotlist(in_buf, encoding, opts).unwrap_or_else(|e| {
+        reporter.print_error_and_exit("Error while parsing hotlist file", e, 1);
+    });
+
+    reporter.print_warnings(&parsed.warnings);
+
+    if !parsed.errors.is_empty() {
+        reporter.print_parse_errors(&parsed.errors);
+        if !opts.lenient {
+            std::process::exit(1);
+        }
+    }
+
+    parsed.hotlist
+}
+
+// Before there were subcommands, hl2html only converted, e.g. `hl2html -f adr notes.adr`. Those
+// invocations still work: anything that doesn't start with a subcommand is converted.
+fn top_level_args() -> TopLevel {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let is_command = |arg: &str| {
+        <Command as argh::SubCommands>::COMMANDS
+            .iter()
+            .any(|c| c.name == arg)
+    };
+    match args.first().map(String::as_str) {
+        None | Some("help") | Some("--help") => {}
+        Some(arg) if is_command(arg) => {}
+        Some(_) => args.insert(0, "convert".to_string()),
+    }
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    TopLevel::from_args(&["hl2html"], &args).unwrap_or_else(|early_exit| {
+        // Same as argh::from_env: help goes to stdout, usage errors to stderr.
+        let code = match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                0
+            }
+            Err(()) => {
+                eprintln!("{}", early_exit.output);
+                1
+            }
+        };
+        std::process::exit(code);
+    })
+}
+
 fn main() {
-    let args: HotlistArgs = argh::from_env();
+    match top_level_args().command {
+        Command::Convert(args) => convert_main(args),
+        Command::Check(args) => check_main(args),
+        Command::Stats(args) => stats_main(args),
+        Command::Search(args) => search_main(args),
+        Command::Diff(args) => diff_main(args),
+        Command::Merge(args) => merge_main(args),
+        Command::Dump(args) => dump_main(args),
+    }
+}
+
+fn convert_main(args: ConvertArgs) {
     let mut in_buf = String::new();
+    let opts = args.parse_options();
 
-    let hotlist = parser::parse_hotlist_from_file(&args.path, &mut in_buf).unwrap_or_else(|e| {
-        println!("Error while parsing hotlist file:");
-        error::print_error_and_exit(e, &args.path, 1);
+    let reporter = Reporter::new(&args.path, args.message_format);
+    let query = args.select.as_deref().map(|q| {
+        select::Query::parse(q).unwrap_or_else(|e| {
+            reporter.print_error_and_exit("Error in --select query", e.into(), 1);
+        })
     });
 
+    let mut hotlist = load_hotlist(&args.path, &mut in_buf, &opts, args.message_format);
+    if let Some(query) = &query {
+        hotlist.select(query);
+    }
+
     match args.format {
+        OutputFormat::Adr => {
+            gen::emit_hotlist_as_adr((&args.output).as_ref(), &hotlist, args.multiple)
+                .unwrap_or_else(|e| {
+                    let context = format!(
+                        "Error while writing hotlist file {}",
+                        args.output.as_deref().unwrap_or("to stdout")
+                    );
+                    reporter.print_error_and_exit(&context, e, 6);
+                });
+        }
         OutputFormat::Html => {
-            gen::emit_hotlist_as_html((&args.output).as_ref(), &hotlist, args.multiple)
+            gen::emit_hotlist_as_html(args.output.as_ref(), &hotlist, args.multiple, args.trash)
                 .unwrap_or_else(|e| {
-                    if args.multiple {
-                        println!("Error while writing HTML files:");
+                    let context = if args.multiple {
+                        "Error while writing HTML files".to_string()
                     } else {
-                        println!(
-                            "Error while writing HTML file {}:",
-                            &args.output.unwrap_or("to stdout".to_string())
-                        );
-                    }
-                    error::print_error_and_exit(e, &args.path, 2);
+                        format!(
+                            "Error while writing HTML file {}",
+                            args.output.as_deref().unwrap_or("to stdout")
+                        )
+                    };
+                    reporter.print_error_and_exit(&context, e, 2);
                 });
         }
         OutputFormat::Markdown => {
-            println!(
-                "Error while writing Markdown file {}:",
-                &args.output.unwrap_or("to stdout".to_string())
-            );
-            error::print_error_and_exit(
+            reporter.print_error_and_exit(
+                &format!(
+                    "Error while writing Markdown file {}",
+                    &args.output.unwrap_or("to stdout".to_string())
+                ),
                 "markdown output not yet implemented".into(),
-                &args.path,
                 3,
             );
         }
         OutputFormat::TiddlerJson => {
-            gen::emit_hotlist_as_tiddler_json((&args.output).as_ref(), &hotlist, args.multiple)
-                .unwrap_or_else(|e| {
-                    println!(
-                        "Error while writing Tiddler JSON file {}:",
-                        &args.output.unwrap_or("to stdout".to_string())
-                    );
-                    error::print_error_and_exit(e, &args.path, 4);
-                });
+            gen::emit_hotlist_as_tiddler_json(
+                args.output.as_ref(),
+                &hotlist,
+                args.multiple,
+                args.trash,
+            )
+            .unwrap_or_else(|e| {
+                let context = format!(
+                    "Error while writing Tiddler JSON file {}",
+                    args.output.as_deref().unwrap_or("to stdout")
+                );
+                reporter.print_error_and_exit(&context, e, 4);
+            });
         }
         OutputFormat::WikiText => {
-            println!(
-                "Error while writing WikiText file {}:",
-                &args.output.unwrap_or("to stdout".to_string())
-            );
-            error::print_error_and_exit(
+            reporter.print_error_and_exit(
+                &format!(
+                    "Error while writing WikiText file {}",
+                    &args.output.unwrap_or("to stdout".to_string())
+                ),
                 "wikitext output not yet implemented".into(),
-                &args.path,
                 5,
             );
         }
     }
 }
+
+fn merge_main(args: MergeArgs) {
+    let opts = args.parse_options();
+
+    if args.paths.len() < 2 {
+        eprintln!("hl2html merge: at least two input files are required");
+        std::process::exit(1);
+    }
+
+    // Each file has its own buffer, so the hotlists are copied out of them.
+    let mut hotlists = args.paths.iter().map(|path| {
+        let mut in_buf = String::new();
+        load_hotlist(path, &mut in_buf, &opts, args.message_format).into_owned()
+    });
+    let first = hotlists.next().unwrap();
+
+    // A conflict involves two of the files, and an output error none of them.
+    let reporter = Reporter::without_file(args.message_format);
+
+    let merged = merge::merge(first, hotlists, args.on_conflict).unwrap_or_else(|e| {
+        reporter.print_error_and_exit("Error while merging hotlists", e.into(), 7);
+    });
+
+    gen::emit_hotlist_as_adr(args.output.as_ref(), &merged, false).unwrap_or_else(|e| {
+        let context = format!(
+            "Error while writing hotlist file {}",
+            args.output.as_deref().unwrap_or("to stdout")
+        );
+        reporter.print_error_and_exit(&context, e, 6);
+    });
+}
+
+fn diff_main(args: DiffArgs) {
+    let opts = args.parse_options();
+
+    let mut old_buf = String::new();
+    let old = load_hotlist(&args.old, &mut old_buf, &opts, args.message_format);
+    let mut new_buf = String::new();
+    let new = load_hotlist(&args.new, &mut new_buf, &opts, args.message_format);
+
+    for d in diff::diff(&old, &new) {
+        match args.format {
+            ReportFormat::Human => println!("{}", d),
+            ReportFormat::Json => println!("{}", d.to_json()),
+        }
+    }
+}
+
+fn search_main(args: SearchArgs) {
+    let opts = args.parse_options();
+    let search_opts = search::SearchOptions {
+        regex: args.regex,
+        case_insensitive: args.ignore_case,
+    };
+
+    let reporter = Reporter::new(&args.path, args.message_format);
+    let searcher = search::Searcher::new(&args.pattern, search_opts).unwrap_or_else(|e| {
+        reporter.print_error_and_exit("Error in search pattern", e.into(), 1);
+    });
+
+    let mut in_buf = String::new();
+    let hotlist = load_hotlist(&args.path, &mut in_buf, &opts, args.message_format);
+
+    // Escape codes would end up as junk in a file or another program's input.
+    let highlight = match args.color {
+        Color::Auto => io::stdout().is_terminal(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+    let (hl_start, hl_end) = if highlight {
+        ("\x1b[1;31m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+
+    for hit in searcher.search(&hotlist) {
+        let snippet = hit.snippet(30);
+        println!(
+            "{}  {} {}  {}  {}: {}{}{}{}{}{}{}",
+            ast::folder_path(&hit.folder),
+            hit.kind,
+            hit.id,
+            hit.timestamp.format("%Y-%m-%d"),
+            hit.field,
+            if snippet.more_before { "..." } else { "" },
+            snippet.before,
+            hl_start,
+            // A regex can match across line breaks.
+            snippet.matched.replace('\n', " "),
+            hl_end,
+            snippet.after,
+            if snippet.more_after { "..." } else { "" },
+        );
+    }
+}
+
+fn stats_main(args: StatsArgs) {
+    let opts = args.parse_options();
+
+    let mut in_buf = String::new();
+    let hotlist = load_hotlist(&args.path, &mut in_buf, &opts, args.message_format);
+    let stats = stats::Stats::new(&hotlist);
+
+    match args.format {
+        ReportFormat::Human => print!("{}", stats.display(args.top_hosts)),
+        ReportFormat::Json => println!("{}", stats.to_json(args.top_hosts)),
+    }
+}
+
+fn check_main(args: CheckArgs) {
+    let opts = args.parse_options();
+    let mut rules = check::Rules::default();
+    for (rule, severity) in &args.rule {
+        rules.set(*rule, *severity);
+    }
+
+    let mut in_buf = String::new();
+    let hotlist = load_hotlist(&args.path, &mut in_buf, &opts, args.message_format);
+    let findings = check::check(&hotlist, &rules, chrono::Utc::now());
+
+    for f in &findings {
+        match args.format {
+            ReportFormat::Human => println!("{}", f),
+            ReportFormat::Json => println!("{}", f.to_json()),
+        }
+    }
+
+    std::process::exit(check::exit_code(&findings));
+}
+
+fn dump_main(args: DumpArgs) {
+    let opts = args.parse_options();
+
+    let mut in_buf = String::new();
+    let hotlist = load_hotlist(&args.path, &mut in_buf, &opts, args.message_format);
+
+    if !args.json {
+        println!("{:#?}", hotlist);
+        return;
+    }
+
+    #[cfg(feature = "serialize")]
+    {
+        let reporter = Reporter::new(&args.path, args.message_format);
+        let json = serde_json::to_string_pretty(&hotlist).unwrap_or_else(|e| {
+            reporter.print_error_and_exit("Error while serializing hotlist", e.into(), 1);
+        });
+        println!("{}", json);
+    }
+
+    #[cfg(not(feature = "serialize"))]
+    {
+        eprintln!("hl2html dump: --json needs hl2html to be built with the serialize feature");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,461 @@
+// Combining hotlists, e.g. copies of notes.adr from several machines. Entries are matched by UUID
+// and folders also by path, so merging a hotlist with an older copy of itself only adds what's new.
+// Entries that are added keep their ID unless it's already taken, in which case they get one after
+// the highest ID in use.
+
+use crate::ast::{walk, Encoding, EntryKind, Folder, Hotlist, Options, Version};
+
+use std::collections::HashSet;
+use std::error;
+use std::fmt;
+
+use uuid::Uuid;
+
+// What to do when both hotlists have an entry with the same UUID, but the entries differ.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConflictPolicy {
+    // The entry with the later timestamp replaces the other, keeping its place and ID. On a tie,
+    // the entry that was merged into stays.
+    Newest,
+    // The incoming entry is added next to the existing one with a new UUID.
+    KeepBoth,
+    Fail,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MergeError {
+    Conflict(Uuid),
+    // An added entry's ID is taken, and every ID after the highest one in use is too.
+    OutOfIds,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::Conflict(uuid) => {
+                write!(f, "entries with UUID {} differ", uuid.to_hyphenated_ref())
+            }
+            MergeError::OutOfIds => write!(f, "no IDs left after the highest one in use"),
+        }
+    }
+}
+
+impl error::Error for MergeError {}
+
+// Merges each of others into base in turn.
+pub fn merge<'a, I>(
+    mut base: Hotlist<'a>,
+    others: I,
+    policy: ConflictPolicy,
+) -> Result<Hotlist<'a>, MergeError>
+where
+    I: IntoIterator<Item = Hotlist<'a>>,
+{
+    for other in others {
+        base.merge(other, policy)?;
+    }
+
+    Ok(base)
+}
+
+impl<'a> Hotlist<'a> {
+    pub fn merge(&mut self, other: Hotlist<'a>, policy: ConflictPolicy) -> Result<(), MergeError> {
+        // Text from different code pages can only be written back out together as UTF-8.
+        if self.options.encoding != other.options.encoding {
+            self.options = Options {
+                encoding: Encoding::Utf8(Version::parse("3").unwrap()),
+            };
+        }
+
+        let mut merger = Merger {
+            used_ids: collect_ids(&self.entries),
+            next_id: self.next_id(),
+            hl: self,
+            policy,
+        };
+
+        for entry in other.entries {
+            merger.merge_entry(None, entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Merger<'h, 'a> {
+    hl: &'h mut Hotlist<'a>,
+    used_ids: HashSet<u32>,
+    // None once u32::MAX is in use.
+    next_id: Option<u32>,
+    policy: ConflictPolicy,
+}
+
+impl<'h, 'a> Merger<'h, 'a> {
+    // parent is the ID of the folder in hl that corresponds to the incoming entry's folder.
+    fn merge_entry(&mut self, parent: Option<u32>, entry: EntryKind<'a>) -> Result<(), MergeError> {
+        let existing = entry.uuid().and_then(|u| self.hl.entry_by_uuid(&u));
+
+        match (existing, entry) {
+            // The folder's own fields are resolved like any other entry, then its entries are
+            // merged one by one.
+            (Some(EntryKind::Folder(f)), EntryKind::Folder(mut other)) => {
+                let id = f.id;
+                if !same_folder_fields(f, &other) {
+                    let newer = other.timestamp > f.timestamp;
+                    match self.policy {
+                        ConflictPolicy::Newest if newer => self.update_folder(&mut other),
+                        ConflictPolicy::Newest => {}
+                        // The entries that are already in the existing folder stay there, so the
+                        // new folder only gets what's new.
+                        ConflictPolicy::KeepBoth => {
+                            return self.add_folder(
+                                parent,
+                                Folder {
+                                    uuid: Uuid::new_v4(),
+                                    ..other
+                                },
+                            );
+                        }
+                        ConflictPolicy::Fail => return Err(MergeError::Conflict(other.uuid)),
+                    }
+                }
+                self.merge_folder_contents(id, other)
+            }
+            (Some(_), EntryKind::Folder(other)) => {
+                let uuid = other.uuid;
+                self.resolve_kind_clash(uuid)?;
+                self.add_folder(
+                    parent,
+                    Folder {
+                        uuid: Uuid::new_v4(),
+                        ..other
+                    },
+                )
+            }
+            (None, EntryKind::Folder(other)) => match self.matching_folder(parent, &other) {
+                Some(id) => self.merge_folder_contents(id, other),
+                None => self.add_folder(parent, other),
+            },
+            (Some(e), mut other) => {
+                let id = e.id();
+                set_id(&mut other, id);
+                if *e == other {
+                    return Ok(());
+                }
+
+                let uuid = other.uuid().unwrap();
+                if matches!(e, EntryKind::Folder(_)) {
+                    self.resolve_kind_clash(uuid)?;
+                    set_uuid(&mut other, Uuid::new_v4());
+                    return self.add(parent, other);
+                }
+
+                match self.policy {
+                    ConflictPolicy::Newest => {
+                        if other.timestamp() > e.timestamp() {
+                            *self.hl.entry_by_uuid_mut(&uuid).unwrap() = other;
+                        }
+                        Ok(())
+                    }
+                    ConflictPolicy::KeepBoth => {
+                        set_uuid(&mut other, Uuid::new_v4());
+                        self.add(parent, other)
+                    }
+                    ConflictPolicy::Fail => Err(MergeError::Conflict(uuid)),
+                }
+            }
+            // There's no telling whether a bare separator is already there, so they're only kept
+            // in folders that are new.
+            (None, EntryKind::Separator(_)) => Ok(()),
+            (None, other) => self.add(parent, other),
+        }
+    }
+
+    // A folder whose UUID belongs to a note or the like elsewhere can't replace it, so unless
+    // conflicts are errors, both are kept.
+    fn resolve_kind_clash(&self, uuid: Uuid) -> Result<(), MergeError> {
+        match self.policy {
+            ConflictPolicy::Fail => Err(MergeError::Conflict(uuid)),
+            _ => Ok(()),
+        }
+    }
+
+    // The folder in parent that an incoming folder with a new UUID should join: the trash folder
+    // for a trash folder, otherwise the first with the same name.
+    fn matching_folder(&mut self, parent: Option<u32>, folder: &Folder) -> Option<u32> {
+        if folder.trash && parent.is_none() {
+            if let Some(trash) = self.hl.trash_folder() {
+                return Some(trash.id);
+            }
+        }
+
+        self.entries(parent).iter().find_map(|e| match e {
+            EntryKind::Folder(f) if f.name == folder.name && !f.trash => Some(f.id),
+            _ => None,
+        })
+    }
+
+    // Gives the existing folder with the same UUID the incoming folder's name and flags. It keeps
+    // its ID, place and entries, and only becomes the trash folder if it's at the top level and
+    // there isn't one already.
+    fn update_folder(&mut self, other: &mut Folder<'a>) {
+        let uuid = other.uuid;
+        let can_be_trash = self.hl.entries.iter().any(|e| e.uuid() == Some(uuid))
+            && !matches!(self.hl.trash_folder(), Some(t) if t.uuid != uuid);
+
+        if let Some(EntryKind::Folder(f)) = self.hl.entry_by_uuid_mut(&uuid) {
+            f.name = std::mem::take(&mut other.name);
+            f.timestamp = other.timestamp;
+            f.trash = other.trash && can_be_trash;
+            f.expanded = other.expanded;
+            f.extra = std::mem::take(&mut other.extra);
+        }
+    }
+
+    fn merge_folder_contents(&mut self, id: u32, folder: Folder<'a>) -> Result<(), MergeError> {
+        for entry in folder.entries {
+            self.merge_entry(Some(id), entry)?;
+        }
+        Ok(())
+    }
+
+    // Adds the folder on its own, then merges its entries into it, since they may already be
+    // elsewhere in hl.
+    fn add_folder(
+        &mut self,
+        parent: Option<u32>,
+        mut folder: Folder<'a>,
+    ) -> Result<(), MergeError> {
+        // Only one trash folder, at the top level.
+        if folder.trash && (parent.is_some() || self.hl.trash_folder().is_some()) {
+            folder.trash = false;
+        }
+
+        let entries = std::mem::take(&mut folder.entries);
+        let id = self.claim_id(folder.id)?;
+        folder.id = id;
+        self.entries(parent).push(EntryKind::Folder(folder));
+
+        for entry in entries {
+            match entry {
+                EntryKind::Separator(s) if s.uuid.is_none() => {
+                    self.add(Some(id), EntryKind::Separator(s))?
+                }
+                _ => self.merge_entry(Some(id), entry)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, parent: Option<u32>, mut entry: EntryKind<'a>) -> Result<(), MergeError> {
+        let id = entry.id().map(|id| self.claim_id(id)).transpose()?;
+        set_id(&mut entry, id);
+        self.entries(parent).push(entry);
+        Ok(())
+    }
+
+    fn claim_id(&mut self, id: u32) -> Result<u32, MergeError> {
+        let id = if self.used_ids.contains(&id) {
+            let next = self.next_id.ok_or(MergeError::OutOfIds)?;
+            self.next_id = next.checked_add(1);
+            next
+        } else {
+            id
+        };
+
+        self.used_ids.insert(id);
+        if matches!(self.next_id, Some(next) if id >= next) {
+            self.next_id = id.checked_add(1);
+        }
+        Ok(id)
+    }
+
+    fn entries(&mut self, parent: Option<u32>) -> &mut Vec<EntryKind<'a>> {
+        self.hl
+            .folder_entries_mut(parent)
+            .expect("merge only adds to folders it found or created")
+    }
+}
+
+// Everything but the ID and the entries.
+fn same_folder_fields(a: &Folder, b: &Folder) -> bool {
+    a.name == b.name
+        && a.timestamp == b.timestamp
+        && a.trash == b.trash
+        && a.expanded == b.expanded
+        && a.extra == b.extra
+}
+
+fn collect_ids(entries: &[EntryKind]) -> HashSet<u32> {
+    let mut ids = HashSet::new();
+    walk(entries, |_, e| ids.extend(e.id()));
+    ids
+}
+
+// Only separators can be without an ID or UUID.
+fn set_id(entry: &mut EntryKind, id: Option<u32>) {
+    match entry {
+        EntryKind::Folder(f) => f.id = id.unwrap_or(f.id),
+        EntryKind::Note(n) => n.id = id.unwrap_or(n.id),
+        EntryKind::Bookmark(b) => b.id = id.unwrap_or(b.id),
+        EntryKind::Contact(c) => c.id = id.unwrap_or(c.id),
+        EntryKind::Separator(s) => s.id = id,
+    }
+}
+
+fn set_uuid(entry: &mut EntryKind, uuid: Uuid) {
+    match entry {
+        EntryKind::Folder(f) => f.uuid = uuid,
+        EntryKind::Note(n) => n.uuid = uuid,
+        EntryKind::Bookmark(b) => b.uuid = uuid,
+        EntryKind::Contact(c) => c.uuid = uuid,
+        EntryKind::Separator(s) => s.uuid = Some(uuid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, ConflictPolicy, MergeError};
+    use crate::ast::{EntryKind, Hotlist, Note};
+    use crate::test_support::{hotlist, notes, uuid};
+
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    // A note was added at the top level since the copies split, which took ID 11.
+    fn base() -> Hotlist<'static> {
+        let mut hl = hotlist();
+        hl.insert(None, EntryKind::Note(Note::new("Qux."))).unwrap();
+        hl
+    }
+
+    // Another machine's copy: Work was made separately there, Ticket was edited later, and Baz was
+    // added to Work, also with ID 11.
+    fn other() -> Hotlist<'static> {
+        let mut hl = hotlist();
+        match hl.entry_mut(2) {
+            Some(EntryKind::Folder(f)) => f.uuid = Uuid::new_v4(),
+            e => panic!("unexpected entry {:?}", e),
+        }
+        match hl.entry_mut(5) {
+            Some(EntryKind::Note(n)) => {
+                n.contents = Some("Ticket, edited.".into());
+                n.timestamp = Utc.timestamp(1300000000, 0);
+            }
+            e => panic!("unexpected entry {:?}", e),
+        }
+        hl.insert(Some(2), EntryKind::Note(Note::new("Baz.")))
+            .unwrap();
+        hl
+    }
+
+    // Work was renamed later on another machine.
+    fn renamed_work() -> Hotlist<'static> {
+        let mut hl = hotlist();
+        match hl.entry_mut(2) {
+            Some(EntryKind::Folder(f)) => {
+                f.name = "Work, renamed".into();
+                f.timestamp = Utc.timestamp(1300000000, 0);
+                f.expanded = true;
+            }
+            e => panic!("unexpected entry {:?}", e),
+        }
+        hl
+    }
+
+    #[test]
+    fn test_merge_same() {
+        let base = base();
+        let hl = merge(base.clone(), vec![base.clone()], ConflictPolicy::Fail).unwrap();
+        assert_eq!(hl, base);
+    }
+
+    #[test]
+    fn test_conflict_newest() {
+        let hl = merge(base(), vec![other()], ConflictPolicy::Newest).unwrap();
+        let work = hl.folder_by_path(&["Work"]).unwrap();
+        assert_eq!(work.id, 2);
+        assert_eq!(
+            notes(&work.entries),
+            vec![(5, "Ticket, edited."), (12, "Baz.")]
+        );
+
+        // Merging the other way around, the newer note still wins.
+        let hl = merge(other(), vec![base()], ConflictPolicy::Newest).unwrap();
+        let work = hl.folder_by_path(&["Work"]).unwrap();
+        assert_eq!(
+            notes(&work.entries),
+            vec![(5, "Ticket, edited."), (11, "Baz.")]
+        );
+    }
+
+    #[test]
+    fn test_conflict_keep_both() {
+        let hl = merge(base(), vec![other()], ConflictPolicy::KeepBoth).unwrap();
+        let work = hl.folder_by_path(&["Work"]).unwrap();
+        assert_eq!(
+            notes(&work.entries),
+            vec![(5, "Ticket."), (12, "Ticket, edited."), (13, "Baz.")]
+        );
+        let edited = hl.entry(12).and_then(|e| e.uuid());
+        assert_ne!(edited, Some(uuid(5)));
+    }
+
+    #[test]
+    fn test_conflict_fail() {
+        assert_eq!(
+            merge(base(), vec![other()], ConflictPolicy::Fail),
+            Err(MergeError::Conflict(uuid(5)))
+        );
+    }
+
+    #[test]
+    fn test_folder_conflict_newest() {
+        let hl = merge(hotlist(), vec![renamed_work()], ConflictPolicy::Newest).unwrap();
+        assert!(hl.folder_by_path(&["Work"]).is_none());
+        let work = hl.folder_by_path(&["Work, renamed"]).unwrap();
+        assert_eq!(work.id, 2);
+        assert!(work.expanded);
+        assert_eq!(notes(&work.entries), vec![(5, "Ticket.")]);
+
+        // Merging the other way around, the newer name still wins.
+        let hl = merge(renamed_work(), vec![hotlist()], ConflictPolicy::Newest).unwrap();
+        assert!(hl.folder_by_path(&["Work"]).is_none());
+        assert!(hl.folder_by_path(&["Work, renamed"]).is_some());
+    }
+
+    // The entries that are already in Work stay there, so the copy is left empty.
+    #[test]
+    fn test_folder_conflict_keep_both() {
+        let hl = merge(hotlist(), vec![renamed_work()], ConflictPolicy::KeepBoth).unwrap();
+        let work = hl.folder_by_path(&["Work"]).unwrap();
+        assert_eq!(notes(&work.entries), vec![(5, "Ticket.")]);
+        let copy = hl.folder_by_path(&["Work, renamed"]).unwrap();
+        assert_ne!(copy.uuid, work.uuid);
+        assert_eq!(copy.id, 11);
+        assert!(copy.entries.is_empty());
+    }
+
+    #[test]
+    fn test_folder_conflict_fail() {
+        assert_eq!(
+            merge(hotlist(), vec![renamed_work()], ConflictPolicy::Fail),
+            Err(MergeError::Conflict(uuid(2)))
+        );
+    }
+
+    #[test]
+    fn test_out_of_ids() {
+        let mut base = hotlist();
+        match base.entry_mut(5) {
+            Some(EntryKind::Note(n)) => n.id = u32::MAX,
+            e => panic!("unexpected entry {:?}", e),
+        }
+
+        // The edited Ticket is kept next to the original, but the only free IDs are past u32::MAX.
+        assert_eq!(
+            merge(base, vec![other()], ConflictPolicy::KeepBoth),
+            Err(MergeError::OutOfIds)
+        );
+    }
+}
@@ -7,70 +7,362 @@ use std::io::prelude::*;
 use std::io::BufReader;
 use std::path::Path;
 
-use lalrpop_util::lalrpop_mod;
+use chrono::{DateTime, TimeZone, Utc};
+use encoding_rs::Encoding;
+use lalrpop_util::{lalrpop_mod, ParseError};
+use url::Url;
+use uuid::Uuid;
 lalrpop_mod!(
     #[cfg_attr(not(test), allow(dead_code, unused_imports))]
     pub hotlist
 ); // synthesized by LALRPOP
 
+// One-based; the column counts chars, not bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct LineInfo {
     pub line: usize,
-    pub offset: usize,
+    pub column: usize,
 }
 
-pub fn parse_hotlist_from_file<'a, T: AsRef<Path>>(
-    filename: T,
-    in_buf: &'a mut String,
-) -> Result<ast::Hotlist<'a>, Error<'a>> {
-    let file = File::open(filename)?;
-    let mut buf_reader = BufReader::new(file);
+// Maps byte offsets into the decoded hotlist back to lines, so diagnostics don't have to rescan
+// the text for each error.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
 
-    buf_reader.read_to_string(&mut *in_buf)?;
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
 
-    let lexer = lexer::Lexer::new(&*in_buf);
-    let parser = hotlist::HotlistParser::new();
+        Self { text, starts }
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
 
-    let hotlist = parser.parse(in_buf, lexer)?;
+    pub fn locate(&self, offset: usize) -> LineInfo {
+        let offset = self.clamp(offset);
+        let line = match self.starts.binary_search(&offset) {
+            Ok(l) => l,
+            Err(l) => l - 1,
+        };
+        let column = self.text[self.starts[line]..offset].chars().count() + 1;
+
+        LineInfo {
+            line: line + 1,
+            column,
+        }
+    }
 
-    Ok(hotlist)
+    // The text of a one-based line, without its line ending.
+    pub fn line(&self, line: usize) -> &'a str {
+        let start = self.starts[line - 1];
+        let end = self.starts.get(line).copied().unwrap_or(self.text.len());
+
+        self.text[start..end].trim_end_matches(&['\n', '\r'][..])
+    }
+
+    // Offsets come from the lexer and should already be in range and on a char boundary, but a
+    // diagnostic for a bad offset shouldn't panic.
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
 }
 
-pub fn get_line_and_offset<'a, T: Read>(
-    filebuf: &mut T,
-    file_offset: usize,
-) -> Result<LineInfo, Error<'a>> {
-    let mut num_lines = 1;
-    let mut offset_cur_line = 0;
-
-    for (i, b) in filebuf.bytes().enumerate() {
-        if (b? as char) == '\n' {
-            num_lines = num_lines + 1;
-            offset_cur_line = i + 1; // Next line begins one char from now- anticipate it!
+#[derive(Default)]
+pub struct ParseOptions {
+    // Overrides the encoding detected from the hotlist header.
+    pub encoding: Option<&'static Encoding>,
+    // Replace invalid or missing values instead of failing; see ParseContext.
+    pub lenient: bool,
+}
+
+pub type HotlistParseError<'input> =
+    ParseError<usize, lexer::Tok<'input>, lexer::LexerError<'input>>;
+
+// Threaded through the grammar actions. In lenient mode, a bad or missing value is replaced and
+// a warning is recorded, instead of failing the whole parse. Entries that are broken beyond that
+// are skipped, and their errors collected.
+#[derive(Default)]
+pub struct ParseContext<'input> {
+    pub lenient: bool,
+    pub warnings: Vec<ast::HotlistWarning<'input>>,
+    pub errors: Vec<HotlistParseError<'input>>,
+}
+
+impl<'input> ParseContext<'input> {
+    pub fn new(lenient: bool) -> Self {
+        Self {
+            lenient,
+            warnings: Vec::new(),
+            errors: Vec::new(),
         }
+    }
 
-        if i >= file_offset {
-            break;
+    fn recover_entry(
+        &mut self,
+        entry: Result<ast::EntryKind<'input>, Vec<ast::HotlistError<'input>>>,
+    ) -> Option<ast::EntryKind<'input>> {
+        match entry {
+            Ok(e) => Some(e),
+            Err(errors) => {
+                self.errors
+                    .extend(errors.into_iter().map(HotlistParseError::from));
+                None
+            }
+        }
+    }
+
+    fn recover<T>(
+        &mut self,
+        error: ast::HotlistError<'input>,
+        fixup: ast::Fixup,
+        value: T,
+    ) -> Result<T, ast::HotlistError<'input>> {
+        if self.lenient {
+            self.warnings.push(ast::HotlistWarning { error, fixup });
+            Ok(value)
+        } else {
+            Err(error)
+        }
+    }
+
+    fn url(
+        &mut self,
+        s: &'input str,
+        span: (usize, usize),
+    ) -> Result<ast::HotlistUrl<'input>, ast::HotlistError<'input>> {
+        match Url::parse(s) {
+            Ok(u) => Ok(ast::HotlistUrl::Valid(u)),
+            Err(_) => self.recover(
+                ast::HotlistError::InvalidUrl(s, span),
+                ast::Fixup::KeptRawUrl,
+                ast::HotlistUrl::Invalid(s.into()),
+            ),
+        }
+    }
+
+    fn uuid(
+        &mut self,
+        s: &'input str,
+        span: (usize, usize),
+    ) -> Result<Uuid, ast::HotlistError<'input>> {
+        match Uuid::parse_str(s) {
+            Ok(u) => Ok(u),
+            Err(_) => {
+                let u = derive_uuid(s);
+                self.recover(
+                    ast::HotlistError::InvalidUuid(s, span),
+                    ast::Fixup::DerivedUuid(u),
+                    u,
+                )
+            }
         }
     }
 
-    Ok(LineInfo {
-        line: num_lines,
-        offset: (file_offset - offset_cur_line) + 1, // Both file_offset and offset_cur_line are
-                                                     // zero-based, we want one-based.
+    fn required_uuid(
+        &mut self,
+        uuid: Option<Uuid>,
+        id: u32,
+        name: &str,
+        si: ast::SpanInfo,
+    ) -> Result<Uuid, ast::HotlistError<'input>> {
+        match uuid {
+            Some(u) => Ok(u),
+            None => {
+                let u = derive_uuid(&format!("{}:{}", id, name));
+                self.recover(
+                    ast::HotlistError::RequiredFieldMissing("UNIQUEID", si),
+                    ast::Fixup::DerivedUuid(u),
+                    u,
+                )
+            }
+        }
+    }
+
+    fn required_timestamp(
+        &mut self,
+        timestamp: Option<DateTime<Utc>>,
+        si: ast::SpanInfo,
+    ) -> Result<DateTime<Utc>, ast::HotlistError<'input>> {
+        match timestamp {
+            Some(t) => Ok(t),
+            None => {
+                let t = Utc.timestamp(0, 0);
+                self.recover(
+                    ast::HotlistError::RequiredFieldMissing("CREATED", si),
+                    ast::Fixup::DefaultTimestamp(t),
+                    t,
+                )
+            }
+        }
+    }
+}
+
+// Entry actions note every problem with an entry rather than stopping at the first one.
+fn collect_error<'input, T>(
+    errors: &mut Vec<ast::HotlistError<'input>>,
+    res: Result<T, ast::HotlistError<'input>>,
+) -> Option<T> {
+    res.map_err(|e| errors.push(e)).ok()
+}
+
+// The parser sees a line the lexer couldn't read as lexer::Tok::Invalid; report it as the
+// lexer error it stands for.
+fn lexer_error(error: HotlistParseError) -> HotlistParseError {
+    match error {
+        ParseError::UnrecognizedToken {
+            token: (lo, lexer::Tok::Invalid, _),
+            ..
+        } => ParseError::User {
+            error: lexer::LexerError::LexerError { char_idx: lo },
+        },
+        error => error,
+    }
+}
+
+// Name-based UUIDs, so that converting the same file twice gives the same result.
+fn derive_uuid(seed: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, seed.as_bytes())
+}
+
+// A hotlist that parsed, minus any entries listed in errors.
+pub struct ParsedHotlist<'a> {
+    pub hotlist: ast::Hotlist<'a>,
+    pub warnings: Vec<ast::HotlistWarning<'a>>,
+    pub errors: Vec<HotlistParseError<'a>>,
+}
+
+// Reads and decodes a hotlist into in_buf, returning the encoding used. Kept separate from
+// parse_hotlist so that the decoded text is still around to show in diagnostics.
+pub fn read_hotlist_file<T: AsRef<Path>>(
+    filename: T,
+    in_buf: &mut String,
+    opts: &ParseOptions,
+) -> Result<&'static Encoding, Error<'static>> {
+    let file = File::open(filename)?;
+    read_hotlist(BufReader::new(file), in_buf, opts)
+}
+
+pub fn read_hotlist<R: Read>(
+    mut reader: R,
+    in_buf: &mut String,
+    opts: &ParseOptions,
+) -> Result<&'static Encoding, Error<'static>> {
+    let mut raw = Vec::new();
+    reader.read_to_end(&mut raw)?;
+
+    Ok(decode_hotlist(&raw, opts.encoding, in_buf))
+}
+
+// The hotlist borrows from in_buf, so the caller has to keep the text around.
+pub fn parse_hotlist_from_file<'a, T: AsRef<Path>>(
+    filename: T,
+    in_buf: &'a mut String,
+    opts: &ParseOptions,
+) -> Result<ParsedHotlist<'a>, Error<'a>> {
+    let encoding = read_hotlist_file(filename, in_buf, opts)?;
+    parse_hotlist(in_buf, encoding, opts)
+}
+
+pub fn parse_reader<'a, R: Read>(
+    reader: R,
+    in_buf: &'a mut String,
+    opts: &ParseOptions,
+) -> Result<ParsedHotlist<'a>, Error<'a>> {
+    let encoding = read_hotlist(reader, in_buf, opts)?;
+    parse_hotlist(in_buf, encoding, opts)
+}
+
+// For text that's already decoded. A legacy hotlist is taken to be in opts.encoding, or
+// windows-1252 like detect_encoding assumes.
+pub fn parse_str<'a>(in_buf: &'a str, opts: &ParseOptions) -> Result<ParsedHotlist<'a>, Error<'a>> {
+    let encoding = opts.encoding.unwrap_or(encoding_rs::WINDOWS_1252);
+    parse_hotlist(in_buf, encoding, opts)
+}
+
+// encoding is the one read_hotlist_file decoded with; hotlists that don't declare theirs get it.
+pub fn parse_hotlist<'a>(
+    in_buf: &'a str,
+    encoding: &'static Encoding,
+    opts: &ParseOptions,
+) -> Result<ParsedHotlist<'a>, Error<'a>> {
+    let lexer = lexer::RecoveringLexer::new(in_buf);
+    let parser = hotlist::HotlistParser::new();
+    let mut ctx = ParseContext::new(opts.lenient);
+
+    let mut hotlist = parser.parse(in_buf, &mut ctx, lexer).map_err(lexer_error)?;
+
+    if let ast::Encoding::Legacy(_) = hotlist.options.encoding {
+        hotlist.options.encoding = ast::Encoding::Legacy(encoding);
+    }
+
+    Ok(ParsedHotlist {
+        hotlist,
+        warnings: ctx.warnings,
+        errors: ctx.errors,
     })
 }
 
+// Transcodes a raw hotlist into in_buf, returning the encoding that was actually used. A BOM
+// takes priority over both the override and the header.
+fn decode_hotlist(
+    raw: &[u8],
+    encoding: Option<&'static Encoding>,
+    in_buf: &mut String,
+) -> &'static Encoding {
+    let encoding = encoding.unwrap_or_else(|| detect_encoding(raw));
+    let (decoded, used, _) = encoding.decode(raw);
+
+    in_buf.push_str(&decoded);
+    used
+}
+
+// Opera 7 and later declare "encoding = utf8" on the Options line, the second line of the
+// file. Anything else predates Opera's Unicode support.
+fn detect_encoding(raw: &[u8]) -> &'static Encoding {
+    let declares_utf8 = raw
+        .split(|b| *b == b'\n')
+        .take(2)
+        .any(|line| line.windows(4).any(|w| w == b"utf8"));
+
+    if declares_utf8 {
+        encoding_rs::UTF_8
+    } else {
+        encoding_rs::WINDOWS_1252
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::hotlist;
+    use super::{
+        decode_hotlist, derive_uuid, hotlist, parse_reader, parse_str, LineIndex, LineInfo,
+        ParseContext, ParseOptions,
+    };
     use crate::ast;
+    use crate::error::Diagnostic;
+    use crate::gen;
     use crate::lexer;
 
     use chrono::{TimeZone, Utc};
+    use lalrpop_util::ParseError;
 
     use url::Url;
     use uuid::Uuid;
-    use version_compare::version::Version as RefVersion;
+
+    // The byte offsets of the first s in inp.
+    fn span_of(inp: &str, s: &str) -> (usize, usize) {
+        let lo = inp.find(s).unwrap();
+        (lo, lo + s.len())
+    }
 
     #[test]
     fn test_version() {
@@ -78,9 +370,9 @@ mod tests {
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
             hotlist::HotlistVersionParser::new()
-                .parse(inp, lexer)
+                .parse(inp, &mut ParseContext::default(), lexer)
                 .unwrap(),
-            RefVersion::from("2.0").unwrap()
+            ast::Version::parse("2.0").unwrap()
         );
     }
 
@@ -89,8 +381,10 @@ mod tests {
         let inp = "encoding = utf8, version=3";
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
-            hotlist::SingleOpParser::new().parse(inp, lexer).unwrap(),
-            ast::SingleOp::Encoding(ast::Encoding::Utf8(RefVersion::from("3.0").unwrap()))
+            hotlist::SingleOpParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap(),
+            ast::SingleOp::Encoding(ast::Encoding::Utf8(ast::Version::parse("3.0").unwrap()))
         );
     }
 
@@ -100,10 +394,10 @@ mod tests {
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
             hotlist::HotlistOptionsParser::new()
-                .parse(inp, lexer)
+                .parse(inp, &mut ParseContext::default(), lexer)
                 .unwrap(),
             ast::Options {
-                encoding: ast::Encoding::Utf8(RefVersion::from("3.0").unwrap())
+                encoding: ast::Encoding::Utf8(ast::Version::parse("3.0").unwrap())
             }
         );
     }
@@ -114,7 +408,7 @@ mod tests {
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
             hotlist::HotlistOptionsParser::new()
-                .parse(inp, lexer)
+                .parse(inp, &mut ParseContext::default(), lexer)
                 .unwrap_err(),
             ast::HotlistError::RequiredFieldMissing(
                 "encoding",
@@ -134,17 +428,184 @@ mod tests {
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
             hotlist::HotlistHeaderParser::new()
-                .parse(inp, lexer)
+                .parse(inp, &mut ParseContext::default(), lexer)
                 .unwrap(),
             (
-                RefVersion::from("2.0").unwrap(),
+                ast::Version::parse("2.0").unwrap(),
                 ast::Options {
-                    encoding: ast::Encoding::Utf8(RefVersion::from("3.0").unwrap())
+                    encoding: ast::Encoding::Utf8(ast::Version::parse("3.0").unwrap())
                 },
             )
         );
     }
 
+    #[test]
+    fn test_header_without_options() {
+        let inp = "Opera Hotlist version 1.0\n";
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            hotlist::HotlistHeaderParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap(),
+            (
+                ast::Version::parse("1.0").unwrap(),
+                ast::Options {
+                    encoding: ast::Encoding::Legacy(encoding_rs::WINDOWS_1252)
+                },
+            )
+        );
+    }
+
+    #[test]
+    fn test_legacy_encoding() {
+        let raw = b"Opera Hotlist version 1.0\r\n\
+        \r\n\
+        #NOTE\r\n\
+        \tID=1\r\n\
+        \tUNIQUEID=00000000000000000000000000000000\r\n\
+        \tNAME=Caf\xe9 \x80\r\n\
+        \tCREATED=0\r\n";
+
+        let mut in_buf = String::new();
+        assert_eq!(
+            decode_hotlist(raw, None, &mut in_buf),
+            encoding_rs::WINDOWS_1252
+        );
+
+        let lexer = lexer::Lexer::new(&in_buf);
+        let hl = hotlist::HotlistParser::new()
+            .parse(&in_buf, &mut ParseContext::default(), lexer)
+            .unwrap();
+        match &hl.entries[0] {
+            ast::EntryKind::Note(n) => {
+                assert_eq!(n.contents.as_deref(), Some("Caf\u{e9} \u{20ac}"))
+            }
+            _ => panic!("expected a note"),
+        }
+
+        let raw = b"Opera Hotlist version 2.0\n\
+        Options: encoding = utf8, version=3\n\
+        \n\
+        #NOTE\n\
+        \tNAME=Caf\xc3\xa9\n";
+
+        let mut in_buf = String::new();
+        assert_eq!(decode_hotlist(raw, None, &mut in_buf), encoding_rs::UTF_8);
+        assert!(in_buf.ends_with("Caf\u{e9}\n"));
+
+        let mut in_buf = String::new();
+        assert_eq!(
+            decode_hotlist(raw, Some(encoding_rs::WINDOWS_1252), &mut in_buf),
+            encoding_rs::WINDOWS_1252
+        );
+        assert!(in_buf.ends_with("Caf\u{c3}\u{a9}\n"));
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let raw = b"Opera Hotlist version 1.0\r\n\
+        \r\n\
+        #NOTE\r\n\
+        \tID=1\r\n\
+        \tUNIQUEID=00000000000000000000000000000000\r\n\
+        \tNAME=Caf\xe9\r\n\
+        \tCREATED=0\r\n";
+
+        let mut in_buf = String::new();
+        let parsed = parse_reader(&raw[..], &mut in_buf, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parsed.hotlist.options.encoding,
+            ast::Encoding::Legacy(encoding_rs::WINDOWS_1252)
+        );
+
+        let mut out = Vec::new();
+        gen::write_hotlist_as_html(&mut out, &parsed.hotlist, gen::TrashMode::Include).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("Caf\u{e9}"));
+
+        let parsed = parse_str("Opera Hotlist version 2.0\n", &ParseOptions::default()).unwrap();
+        assert!(parsed.hotlist.entries.is_empty());
+    }
+
+    #[test]
+    fn test_into_owned() {
+        fn load() -> ast::HotlistBuf {
+            let in_buf = String::from(
+                "Opera Hotlist version 2.0\n\
+                Options: encoding = utf8, version=3\n\
+                \n\
+                #NOTE\n\
+                \tID=1\n\
+                \tUNIQUEID=00000000000000000000000000000000\n\
+                \tNAME=Foo.\n\
+                \tCREATED=0\n\
+                \tFACE=Bar\n",
+            );
+            parse_str(&in_buf, &ParseOptions::default())
+                .unwrap()
+                .hotlist
+                .into_owned()
+        }
+
+        let hl = std::thread::spawn(load).join().unwrap();
+        assert_eq!(hl.version.as_str(), "2.0");
+        assert_eq!(
+            hl.options.encoding,
+            ast::Encoding::Utf8(ast::Version::parse("3.0").unwrap())
+        );
+        match &hl.entries[0] {
+            ast::EntryKind::Note(n) => {
+                assert_eq!(n.contents.as_deref(), Some("Foo."));
+                assert_eq!(n.extra, vec![("FACE".into(), "Bar".into())]);
+            }
+            _ => panic!("expected a note"),
+        }
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn test_serialize() {
+        let in_buf = "Opera Hotlist version 2.0\n\
+        Options: encoding = utf8, version=3\n\
+        \n\
+        #FOLDER\n\
+        \tID=1\n\
+        \tNAME=Work\n\
+        \tCREATED=0\n\
+        \tUNIQUEID=11111111111111111111111111111111\n\
+        \n\
+        #NOTE\n\
+        \tID=2\n\
+        \tUNIQUEID=22222222222222222222222222222222\n\
+        \tNAME=Foo\x02\x02bar\n\
+        \tURL=http://www.example.com/\n\
+        \tCREATED=1100000000\n\
+        \n\
+        -\n\
+        \n\
+        #SEPARATOR\n";
+        let hl = parse_str(in_buf, &ParseOptions::default()).unwrap().hotlist;
+
+        let json = serde_json::to_value(&hl).unwrap();
+        assert_eq!(json["version"], "2.0");
+        assert_eq!(json["options"]["encoding"]["Utf8"], "3");
+        let note = &json["entries"][0]["Folder"]["entries"][0]["Note"];
+        assert_eq!(note["uuid"], "22222222-2222-2222-2222-222222222222");
+        assert_eq!(note["url"]["Valid"], "http://www.example.com/");
+        assert_eq!(note["timestamp"], "2004-11-09T11:33:20Z");
+
+        let back: ast::HotlistBuf = serde_json::from_value(json).unwrap();
+        assert_eq!(back, hl);
+
+        let mut legacy = hl.clone();
+        legacy.options.encoding = ast::Encoding::Legacy(encoding_rs::WINDOWS_1252);
+        let json = serde_json::to_string(&legacy).unwrap();
+        assert!(json.contains(r#""Legacy":"windows-1252""#));
+        assert_eq!(
+            serde_json::from_str::<ast::HotlistBuf>(&json).unwrap(),
+            legacy
+        );
+    }
+
     #[test]
     fn test_note_with_linebreak() {
         let inp = "#NOTE\n\
@@ -157,18 +618,494 @@ mod tests {
 
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
-            hotlist::NoteEntryParser::new().parse(inp, lexer).unwrap(),
+            hotlist::NoteEntryParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap(),
             ast::Note {
                 id: 18,
                 uuid: Uuid::parse_str("75356378DB08C2429F4BE860ED92596F").unwrap(),
-                contents: Some("This is a fake note with \x02\x02an encoded linebreak."),
-                url: Some(Url::parse("http://www.example.com").unwrap()),
+                contents: Some("This is a fake note with \x02\x02an encoded linebreak.".into()),
+                url: Some(ast::HotlistUrl::Valid(
+                    Url::parse("http://www.example.com").unwrap()
+                )),
                 timestamp: Utc.timestamp(1322363353, 0),
-                active: false
+                active: false,
+                extra: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn test_bookmark() {
+        let inp = "#URL\n\
+        \tID=287\n\
+        \tNAME=Opera Software\n\
+        \tURL=http://www.opera.com/\n\
+        \tCREATED=1195742106\n\
+        \tVISITED=1322363353\n\
+        \tDESCRIPTION=Home of the \x02\x02Opera browser\n\
+        \tSHORT NAME=op\n\
+        \tICONFILE=opera.ico\n\
+        \tUNIQUEID=E4A8A3A0F7B011DCB1E9E5A2AC3B3C6E\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            hotlist::BookmarkEntryParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap(),
+            ast::Bookmark {
+                id: 287,
+                uuid: Uuid::parse_str("E4A8A3A0F7B011DCB1E9E5A2AC3B3C6E").unwrap(),
+                name: "Opera Software".into(),
+                url: Some(ast::HotlistUrl::Valid(
+                    Url::parse("http://www.opera.com/").unwrap()
+                )),
+                description: Some("Home of the \x02\x02Opera browser".into()),
+                short_name: Some("op".into()),
+                timestamp: Utc.timestamp(1195742106, 0),
+                visited: Some(Utc.timestamp(1322363353, 0)),
+                icon_file: Some("opera.ico".into()),
+                active: false,
+                extra: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn test_contact() {
+        let inp = "#CONTACT\n\
+        \tID=15\n\
+        \tNAME=Opera Software\n\
+        \tURL=http://www.opera.com\n\
+        \tCREATED=1116331484\n\
+        \tACTIVE=YES\n\
+        \tMAIL=info@example.com\x02\x02sales@example.com\n\
+        \tPHONE=+47 24 16 40 00\n\
+        \tPOSTALADDRESS=Gjerdrums vei 19\x02\x02Oslo\n\
+        \tICON=Contact0\n\
+        \tUNIQUEID=3B3A1D28C6E5D911A2A6F4C9BDE08B57\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            hotlist::ContactEntryParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap(),
+            ast::Contact {
+                id: 15,
+                uuid: Uuid::parse_str("3B3A1D28C6E5D911A2A6F4C9BDE08B57").unwrap(),
+                name: "Opera Software".into(),
+                url: Some(ast::HotlistUrl::Valid(
+                    Url::parse("http://www.opera.com").unwrap()
+                )),
+                description: None,
+                short_name: None,
+                timestamp: Utc.timestamp(1116331484, 0),
+                mail: Some("info@example.com\x02\x02sales@example.com".into()),
+                phone: Some("+47 24 16 40 00".into()),
+                fax: None,
+                postal_address: Some("Gjerdrums vei 19\x02\x02Oslo".into()),
+                picture_url: None,
+                icon: Some("Contact0".into()),
+                active: true,
+                extra: vec![]
             }
         );
     }
 
+    #[test]
+    fn test_unknown_fields() {
+        let inp = "#FOLDER\n\
+        \tID=10\n\
+        \tNAME=Trash\n\
+        \tCREATED=1322360302\n\
+        \tDESCRIPTION=\n\
+        \tON PERSONALBAR=NO\n\
+        \tTRASH FOLDER=YES\n\
+        \tDELETABLE=NO\n\
+        \tUNIQUEID=A9AAFED0976111DC85AC8946BEF8D2DC\n\
+        \tPERSONALBAR_POS=-1\n\
+        \tTARGET=\n\
+        \tM2INDEXID=3\n\
+        \n\
+        #NOTE\n\
+        \tID=11\n\
+        \tUNIQUEID=75356378DB08C2429F4BE860ED92596F\n\
+        \tNAME=\n\
+        \tCREATED=1322363353\n\
+        \tFACE=Some value with spaces\n\
+        -\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            &hotlist::HotlistEntriesParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap(),
+            &[ast::EntryKind::Folder(ast::Folder {
+                id: 10,
+                uuid: Uuid::parse_str("A9AAFED0976111DC85AC8946BEF8D2DC").unwrap(),
+                name: "Trash".into(),
+                timestamp: Utc.timestamp(1322360302, 0),
+                expanded: false,
+                trash: true,
+                extra: vec![
+                    ("DESCRIPTION".into(), "".into()),
+                    ("ON PERSONALBAR".into(), "NO".into()),
+                    ("DELETABLE".into(), "NO".into()),
+                    ("PERSONALBAR_POS".into(), "-1".into()),
+                    ("TARGET".into(), "".into()),
+                    ("M2INDEXID".into(), "3".into()),
+                ],
+                entries: vec![ast::EntryKind::Note(ast::Note {
+                    id: 11,
+                    uuid: Uuid::parse_str("75356378DB08C2429F4BE860ED92596F").unwrap(),
+                    contents: Some("".into()),
+                    url: None,
+                    timestamp: Utc.timestamp(1322363353, 0),
+                    active: false,
+                    extra: vec![("FACE".into(), "Some value with spaces".into())]
+                })]
+            })]
+        );
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let inp = "#URL\n\
+        \tID=5\n\
+        \tUNIQUEID=75356378DB08C2429F4BE860ED92596F\n\
+        \tNAME=Before the epoch\n\
+        \tCREATED=-86400\n\
+        \tVISITED=4294967296\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        let bookmark = hotlist::BookmarkEntryParser::new()
+            .parse(inp, &mut ParseContext::default(), lexer)
+            .unwrap();
+        assert_eq!(bookmark.timestamp, Utc.ymd(1969, 12, 31).and_hms(0, 0, 0));
+        assert_eq!(bookmark.visited, Some(Utc.timestamp(4294967296, 0)));
+
+        let inp = "#URL\n\
+        \tID=5\n\
+        \tUNIQUEID=75356378DB08C2429F4BE860ED92596F\n\
+        \tNAME=Corrupt\n\
+        \tCREATED=0\n\
+        \tVISITED=-9223372036854775808\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            hotlist::BookmarkEntryParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap_err(),
+            ast::HotlistError::TimestampOutOfRange(
+                "-9223372036854775808",
+                span_of(inp, "-9223372036854775808")
+            )
+            .into()
+        );
+
+        let inp = "#URL\n\
+        \tID=5\n\
+        \tUNIQUEID=75356378DB08C2429F4BE860ED92596F\n\
+        \tNAME=Corrupt\n\
+        \tCREATED=99999999999999999999\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            hotlist::BookmarkEntryParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap_err(),
+            ast::HotlistError::TimestampOutOfRange(
+                "99999999999999999999",
+                span_of(inp, "99999999999999999999")
+            )
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_url_schemes() {
+        let urls = [
+            "ftp://ftp.example.com/pub/file.txt",
+            "file:///C:/Documents%20and%20Settings/notes.txt",
+            "mailto:someone@example.com",
+            "opera:config#UserPrefs|UserJavaScriptFile",
+            "javascript:void(window.open('http://example.com/?a=1,2'))",
+            "http://例え.テスト/パス?q=値",
+        ];
+
+        for u in urls.iter() {
+            let inp = format!(
+                "#NOTE\n\
+                \tID=1\n\
+                \tUNIQUEID=00000000000000000000000000000000\n\
+                \tURL={}\n\
+                \tCREATED=0\n",
+                u
+            );
+
+            let lexer = lexer::Lexer::new(&inp);
+            let note = hotlist::NoteEntryParser::new()
+                .parse(&inp, &mut ParseContext::default(), lexer)
+                .unwrap();
+            assert_eq!(
+                note.url,
+                Some(ast::HotlistUrl::Valid(Url::parse(u).unwrap()))
+            );
+        }
+
+        let inp = "#NOTE\n\
+        \tID=1\n\
+        \tUNIQUEID=00000000000000000000000000000000\n\
+        \tURL=\n\
+        \tCREATED=0\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        let note = hotlist::NoteEntryParser::new()
+            .parse(inp, &mut ParseContext::default(), lexer)
+            .unwrap();
+        assert_eq!(note.url, None);
+
+        let inp = "#NOTE\n\
+        \tID=1\n\
+        \tUNIQUEID=00000000000000000000000000000000\n\
+        \tURL=not a url\n\
+        \tCREATED=0\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            hotlist::NoteEntryParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap_err(),
+            ast::HotlistError::InvalidUrl("not a url", span_of(inp, "not a url")).into()
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        let inp = "#URL\n\
+        \tID=7\n\
+        \tNAME=Example\n\
+        \tURL=not a url\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert!(hotlist::BookmarkEntryParser::new()
+            .parse(inp, &mut ParseContext::default(), lexer)
+            .is_err());
+
+        let lexer = lexer::Lexer::new(inp);
+        let mut ctx = ParseContext::new(true);
+        let bookmark = hotlist::BookmarkEntryParser::new()
+            .parse(inp, &mut ctx, lexer)
+            .unwrap();
+
+        let uuid = derive_uuid("7:Example");
+        assert_eq!(
+            bookmark.url,
+            Some(ast::HotlistUrl::Invalid("not a url".into()))
+        );
+        assert_eq!(bookmark.uuid, uuid);
+        assert_eq!(bookmark.timestamp, Utc.timestamp(0, 0));
+
+        let fixups: Vec<_> = ctx.warnings.iter().map(|w| &w.fixup).collect();
+        assert_eq!(
+            fixups,
+            vec![
+                &ast::Fixup::KeptRawUrl,
+                &ast::Fixup::DerivedUuid(uuid),
+                &ast::Fixup::DefaultTimestamp(Utc.timestamp(0, 0)),
+            ]
+        );
+        assert_eq!(
+            ctx.warnings[0].error,
+            ast::HotlistError::InvalidUrl("not a url", span_of(inp, "not a url"))
+        );
+
+        // Same input, same UUID.
+        let lexer = lexer::Lexer::new(inp);
+        let again = hotlist::BookmarkEntryParser::new()
+            .parse(inp, &mut ParseContext::new(true), lexer)
+            .unwrap();
+        assert_eq!(again.uuid, bookmark.uuid);
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let inp = "Opera Hotlist version 2.0\n\
+        Options: encoding = utf8, version=3\n\
+        \n\
+        #NOTE\n\
+        \tID=1\n\
+        \tUNIQUEID=00000000000000000000000000000001\n\
+        \tCREATED=0\n\
+        \n\
+        #NOTE\n\
+        \tID=4294967296\n\
+        \tUNIQUEID=00000000000000000000000000000002\n\
+        \tCREATED=99999999999999999999\n\
+        \n\
+        #FOLDER\n\
+        \tID=3\n\
+        \tUNIQUEID=00000000000000000000000000000003\n\
+        \tNAME=Folder\n\
+        \tCREATED=0\n\
+        \n\
+        #NOTE\n\
+        \tID=YES\n\
+        \tCREATED=0\n\
+        -\n\
+        \n\
+        #NOTE\n\
+        \tID=5\n\
+        \tUNIQUEID=00000000000000000000000000000005\n\
+        \tCREATED=0\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        let mut ctx = ParseContext::default();
+        let hl = hotlist::HotlistParser::new()
+            .parse(inp, &mut ctx, lexer)
+            .unwrap();
+
+        let ids: Vec<u32> = hl
+            .entries
+            .iter()
+            .map(|e| match e {
+                ast::EntryKind::Note(n) => n.id,
+                ast::EntryKind::Folder(f) => {
+                    assert!(f.entries.is_empty());
+                    f.id
+                }
+                _ => panic!("unexpected entry {:?}", e),
+            })
+            .collect();
+        assert_eq!(ids, vec![1, 3, 5]);
+
+        assert_eq!(ctx.errors.len(), 3);
+        assert_eq!(
+            ctx.errors[0],
+            ast::HotlistError::U32OutOfRange("4294967296", span_of(inp, "4294967296")).into()
+        );
+        assert_eq!(
+            ctx.errors[1],
+            ast::HotlistError::TimestampOutOfRange(
+                "99999999999999999999",
+                span_of(inp, "99999999999999999999")
+            )
+            .into()
+        );
+        assert!(matches!(
+            ctx.errors[2],
+            ParseError::UnrecognizedToken {
+                token: (_, lexer::Tok::Yes, _),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_lexer_error_recovery() {
+        let inp = "Opera Hotlist version 2.0\n\
+        Options: encoding = utf8, version=3\n\
+        \n\
+        #NOTE\n\
+        \tID=1\n\
+        \tUNIQUEID=00000000000000000000000000000001\n\
+        \tCREATED=0\n\
+        \tnot a field\n\
+        \n\
+        #NOTE\n\
+        \tID=2\n\
+        \tUNIQUEID=00000000000000000000000000000002\n\
+        \tCREATED=0\n";
+
+        let res = parse_str(inp, &ParseOptions::default()).unwrap();
+        let ids: Vec<_> = res.hotlist.entries.iter().filter_map(|e| e.id()).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        let (lo, _) = span_of(inp, "not a field");
+        assert_eq!(
+            res.errors,
+            vec![ParseError::User {
+                error: lexer::LexerError::LexerError { char_idx: lo }
+            }]
+        );
+    }
+
+    #[test]
+    fn test_line_index() {
+        let text = "#FOLDER\r\n\tNAME=Földer\r\n\tID=x\n";
+        let lines = LineIndex::new(text);
+
+        assert_eq!(lines.locate(0), LineInfo { line: 1, column: 1 });
+        assert_eq!(lines.line(1), "#FOLDER");
+        assert_eq!(lines.line(2), "\tNAME=Földer");
+
+        // "ö" is two bytes but one column.
+        let er = text.find("er\r\n\tID").unwrap();
+        assert_eq!(
+            lines.locate(er),
+            LineInfo {
+                line: 2,
+                column: 11
+            }
+        );
+        let x = text.find('x').unwrap();
+        assert_eq!(lines.locate(x), LineInfo { line: 3, column: 5 });
+        assert_eq!(lines.locate(text.len()), LineInfo { line: 4, column: 1 });
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let inp = "Opera Hotlist version 2.0\n\
+        Options: encoding = utf8, version=3\n\
+        \n\
+        #NOTE\n\
+        \tID=1\n\
+        \tNAME=Ünïcode\n\
+        \tCREATED=YES\n\
+        \n\
+        #FOLDER\n\
+        \tID=2\n\
+        \tUNIQUEID=00000000000000000000000000000002\n\
+        \tCREATED=0\n\
+        -\n";
+
+        let lexer = lexer::Lexer::new(inp);
+        let mut ctx = ParseContext::default();
+        hotlist::HotlistParser::new()
+            .parse(inp, &mut ctx, lexer)
+            .unwrap();
+        assert_eq!(ctx.errors.len(), 2);
+
+        let lines = LineIndex::new(inp);
+        let diags: Vec<_> = ctx
+            .errors
+            .iter()
+            .map(|e| Diagnostic::new(e, &lines))
+            .collect();
+
+        assert_eq!(diags[0].kind, "UnrecognizedToken");
+        assert_eq!(
+            diags[0].location.map(|(start, _)| start),
+            Some(LineInfo {
+                line: 7,
+                column: 10
+            })
+        );
+        assert!(!diags[0].expected.is_empty());
+
+        // Missing fields point at the entry's header.
+        let header = inp.find("#FOLDER").unwrap();
+        assert_eq!(diags[1].kind, "RequiredFieldMissing");
+        assert_eq!(diags[1].span, Some((header, header + "#FOLDER".len())));
+        assert_eq!(
+            diags[1].location,
+            Some((
+                LineInfo { line: 9, column: 1 },
+                LineInfo { line: 9, column: 8 }
+            ))
+        );
+    }
+
     #[test]
     fn test_two_notes() {
         let inp = "#NOTE\n\
@@ -189,24 +1126,73 @@ mod tests {
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
             &hotlist::HotlistEntriesParser::new()
-                .parse(inp, lexer)
+                .parse(inp, &mut ParseContext::default(), lexer)
                 .unwrap(),
             &[
                 ast::EntryKind::Note(ast::Note {
                     id: 1,
                     uuid: Uuid::parse_str("00000000000000000000000000000000").unwrap(),
-                    contents: Some("Foo."),
-                    url: Some(Url::parse("https://www.example.com/a/random/path").unwrap()),
+                    contents: Some("Foo.".into()),
+                    url: Some(ast::HotlistUrl::Valid(
+                        Url::parse("https://www.example.com/a/random/path").unwrap()
+                    )),
                     timestamp: Utc.timestamp(0, 0),
-                    active: false
+                    active: false,
+                    extra: vec![]
                 }),
                 ast::EntryKind::Note(ast::Note {
                     id: 2,
                     uuid: Uuid::parse_str("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF").unwrap(),
-                    contents: Some("Bar."),
-                    url: Some(Url::parse("http://www.example.org/path/to/file").unwrap()),
+                    contents: Some("Bar.".into()),
+                    url: Some(ast::HotlistUrl::Valid(
+                        Url::parse("http://www.example.org/path/to/file").unwrap()
+                    )),
                     timestamp: Utc.timestamp(2147483647, 0),
-                    active: true
+                    active: true,
+                    extra: vec![]
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_separators() {
+        let inp = "#NOTE\n\
+        \tID=1\n\
+        \tUNIQUEID=00000000000000000000000000000000\n\
+        \tCREATED=0\n\
+        \n\
+        #SEPARATOR\n\
+        \tID=2\n\
+        \tUNIQUEID=FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF\n\
+        \n\
+        #SEPARATOR\n\
+        \n";
+
+        let lexer = lexer::Lexer::new(inp);
+        assert_eq!(
+            &hotlist::HotlistEntriesParser::new()
+                .parse(inp, &mut ParseContext::default(), lexer)
+                .unwrap(),
+            &[
+                ast::EntryKind::Note(ast::Note {
+                    id: 1,
+                    uuid: Uuid::parse_str("00000000000000000000000000000000").unwrap(),
+                    contents: None,
+                    url: None,
+                    timestamp: Utc.timestamp(0, 0),
+                    active: false,
+                    extra: vec![]
+                }),
+                ast::EntryKind::Separator(ast::Separator {
+                    id: Some(2),
+                    uuid: Some(Uuid::parse_str("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF").unwrap()),
+                    extra: vec![],
+                }),
+                ast::EntryKind::Separator(ast::Separator {
+                    id: None,
+                    uuid: None,
+                    extra: vec![],
                 }),
             ]
         );
@@ -249,32 +1235,37 @@ mod tests {
         let lexer = lexer::Lexer::new(inp);
         assert_eq!(
             &hotlist::HotlistEntriesParser::new()
-                .parse(inp, lexer)
+                .parse(inp, &mut ParseContext::default(), lexer)
                 .unwrap(),
             &[
                 ast::EntryKind::Folder(ast::Folder {
                     id: 100,
                     uuid: Uuid::parse_str("11111111111111111111111111111111").unwrap(),
-                    name: "My Folder",
+                    name: "My Folder".into(),
                     timestamp: Utc.timestamp(900000000, 0),
                     expanded: true,
                     trash: false,
+                    extra: vec![],
                     entries: vec![
                         ast::EntryKind::Note(ast::Note {
                             id: 200,
                             uuid: Uuid::parse_str("DEADCAFEDEADBEEFFEEDCAFEBAADF00D").unwrap(),
-                            contents: Some("Baz."),
-                            url: Some(Url::parse("https://www.example.net").unwrap()),
+                            contents: Some("Baz.".into()),
+                            url: Some(ast::HotlistUrl::Valid(
+                                Url::parse("https://www.example.net").unwrap()
+                            )),
                             timestamp: Utc.timestamp(1000000000, 0),
-                            active: false
+                            active: false,
+                            extra: vec![]
                         }),
                         ast::EntryKind::Folder(ast::Folder {
                             id: 238,
                             uuid: Uuid::parse_str("A9AAFED0976111DC85AC8946BEF8D2DC").unwrap(),
-                            name: "Trash",
+                            name: "Trash".into(),
                             timestamp: Utc.timestamp(1322360302, 0),
                             expanded: false,
                             trash: true,
+                            extra: vec![],
                             entries: vec![]
                         }),
                     ]
@@ -282,10 +1273,13 @@ mod tests {
                 ast::EntryKind::Note(ast::Note {
                     id: 400,
                     uuid: Uuid::parse_str("22222222222222222222222222222222").unwrap(),
-                    contents: Some("Quux."),
-                    url: Some(Url::parse("https://www.example.edu").unwrap()),
+                    contents: Some("Quux.".into()),
+                    url: Some(ast::HotlistUrl::Valid(
+                        Url::parse("https://www.example.edu").unwrap()
+                    )),
                     timestamp: Utc.timestamp(1100000000, 0),
-                    active: true
+                    active: true,
+                    extra: vec![]
                 }),
             ]
         );
@@ -0,0 +1,208 @@
+// Full-text search over a hotlist: note contents, folder names and URLs. Text is searched with its
+// line breaks decoded, so a regex can anchor to the start or end of a line with (?m).
+
+use crate::ast::{decode_line_breaks, walk, EntryKind, Hotlist};
+
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SearchOptions {
+    // Otherwise the pattern is plain text.
+    pub regex: bool,
+    pub case_insensitive: bool,
+}
+
+#[derive(Debug)]
+pub struct Searcher {
+    re: Regex,
+}
+
+// The first match in one field of an entry.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hit<'h> {
+    // Names of the folders the entry is in, from the top level down.
+    pub folder: Vec<&'h str>,
+    pub kind: &'static str,
+    pub id: u32,
+    pub timestamp: DateTime<Utc>,
+    pub field: &'static str,
+    pub text: Cow<'h, str>,
+    // Byte offsets of the match in text.
+    pub start: usize,
+    pub end: usize,
+}
+
+// The part of a hit's text around the match, from the same line or lines.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snippet<'t> {
+    pub before: &'t str,
+    pub matched: &'t str,
+    pub after: &'t str,
+    // Whether text was cut off on either side.
+    pub more_before: bool,
+    pub more_after: bool,
+}
+
+impl Searcher {
+    pub fn new(pattern: &str, opts: SearchOptions) -> Result<Self, regex::Error> {
+        let pattern = if opts.regex {
+            Cow::Borrowed(pattern)
+        } else {
+            Cow::Owned(regex::escape(pattern))
+        };
+
+        let re = RegexBuilder::new(&pattern)
+            .case_insensitive(opts.case_insensitive)
+            .build()?;
+        Ok(Searcher { re })
+    }
+
+    // Hits in the order the entries appear.
+    pub fn search<'h>(&self, hl: &'h Hotlist) -> Vec<Hit<'h>> {
+        let mut hits = Vec::new();
+        walk(&hl.entries, |folders, e| {
+            // Separators have neither, and nothing to search.
+            let (id, timestamp) = match (e.id(), e.timestamp()) {
+                (Some(id), Some(timestamp)) => (id, timestamp),
+                _ => return,
+            };
+
+            for (field, text) in fields(e) {
+                if let Some(m) = self.re.find(&text) {
+                    let (start, end) = (m.start(), m.end());
+                    hits.push(Hit {
+                        folder: folders.iter().map(|f| &*f.name).collect(),
+                        kind: e.kind(),
+                        id,
+                        timestamp,
+                        field,
+                        text,
+                        start,
+                        end,
+                    });
+                }
+            }
+        });
+        hits
+    }
+}
+
+fn fields<'h>(e: &'h EntryKind) -> Vec<(&'static str, Cow<'h, str>)> {
+    let (text, url) = match e {
+        EntryKind::Folder(f) => return vec![("name", Cow::Borrowed(&*f.name))],
+        EntryKind::Note(n) => (n.contents.as_deref(), &n.url),
+        EntryKind::Bookmark(b) => (None, &b.url),
+        EntryKind::Contact(c) => (None, &c.url),
+        EntryKind::Separator(_) => return Vec::new(),
+    };
+
+    let text = text.map(|t| ("contents", decode_line_breaks(t)));
+    let url = url.as_ref().map(|u| ("url", Cow::Owned(u.to_string())));
+    text.into_iter().chain(url).collect()
+}
+
+impl<'h> Hit<'h> {
+    // At most context characters on either side of the match.
+    pub fn snippet(&self, context: usize) -> Snippet<'_> {
+        let text = &*self.text;
+        let line_start = text[..self.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[self.end..]
+            .find('\n')
+            .map_or(text.len(), |i| self.end + i);
+
+        let before = &text[line_start..self.start];
+        let before_start = match context {
+            0 => before.len(),
+            _ => before
+                .char_indices()
+                .rev()
+                .nth(context - 1)
+                .map_or(0, |(i, _)| i),
+        };
+        let before = &before[before_start..];
+
+        let after = &text[self.end..line_end];
+        let after_end = after
+            .char_indices()
+            .nth(context)
+            .map_or(after.len(), |(i, _)| i);
+        let after = &after[..after_end];
+
+        Snippet {
+            before,
+            matched: &text[self.start..self.end],
+            after,
+            more_before: before.len() < self.start,
+            more_after: self.end + after.len() < text.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hit, SearchOptions, Searcher, Snippet};
+    use crate::ast::Hotlist;
+    use crate::test_support::hotlist;
+
+    fn search<'h>(hl: &'h Hotlist, pattern: &str, opts: SearchOptions) -> Vec<Hit<'h>> {
+        Searcher::new(pattern, opts).unwrap().search(hl)
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let hl = hotlist();
+        assert!(search(&hl, "EGG", SearchOptions::default()).is_empty());
+
+        let opts = SearchOptions {
+            case_insensitive: true,
+            ..SearchOptions::default()
+        };
+        let hits = search(&hl, "EGG", opts);
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].kind, hits[0].id), ("note", 7));
+    }
+
+    #[test]
+    fn test_folder() {
+        let hl = hotlist();
+        let hits = search(&hl, "review", SearchOptions::default());
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].folder, vec!["Work", "Old"]);
+    }
+
+    // Only from the line with the match.
+    #[test]
+    fn test_snippet() {
+        let hl = hotlist();
+        let hits = search(&hl, "egg", SearchOptions::default());
+        assert_eq!(
+            hits[0].snippet(10),
+            Snippet {
+                before: "lour, one ",
+                matched: "egg",
+                after: " and milk.",
+                more_before: true,
+                more_after: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_regex() {
+        let hl = hotlist();
+        let opts = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let hits = search(&hl, "(?m)^Fry|cakes", opts);
+        let fields: Vec<_> = hits.iter().map(|h| (h.id, h.field)).collect();
+        assert_eq!(fields, vec![(7, "contents"), (7, "url")]);
+        assert_eq!(hits[0].snippet(20).matched, "cakes");
+
+        assert!(Searcher::new("(", opts).is_err());
+        assert!(search(&hl, "(", SearchOptions::default()).is_empty());
+    }
+}
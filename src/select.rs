@@ -0,0 +1,383 @@
+// A small query language for exporting part of a hotlist, e.g.
+//
+//     folder:"Work/*" and created>=2009-01-01 and url~github.com
+//
+// A query is a set of conditions joined with "and", "or" and "not", with parentheses for grouping.
+// Each condition is a field, an operator and a value, which can be in double quotes:
+//
+// * folder, name, url, contents: ":" matches a glob against the whole value, where "*" stands for
+//   anything and a trailing "/*" also matches the folder itself. "~" searches for a regex.
+// * created: "<", "<=", ">", ">=" or "=" against a YYYY-MM-DD date (UTC).
+//
+// folder is the path of the folder an entry is in, like "Work/Old". Folders have a name, notes
+// contents, and notes, bookmarks and contacts a URL. A condition on a field that an entry doesn't
+// have is false.
+
+use crate::ast::{decode_line_breaks, EntryKind, Hotlist};
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+
+use chrono::NaiveDate;
+use regex::Regex;
+
+#[derive(Debug)]
+pub struct Query {
+    expr: Expr,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueryError {
+    // Byte offset into the query.
+    pub pos: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.pos + 1)
+    }
+}
+
+impl error::Error for QueryError {}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Text(TextField, Regex),
+    Created(DateOp, NaiveDate),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TextField {
+    Folder,
+    Name,
+    Url,
+    Contents,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DateOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let mut p = Parser { s: query, pos: 0 };
+        let expr = p.or_expr()?;
+        p.skip_space();
+        if p.pos < p.s.len() {
+            return Err(p.error("expected \"and\", \"or\" or end of query"));
+        }
+
+        Ok(Query { expr })
+    }
+
+    // folder is the names of the folders the entry is in, from the top level down.
+    pub fn matches<S: AsRef<str>>(&self, entry: &EntryKind, folder: &[S]) -> bool {
+        self.expr.eval(entry, folder)
+    }
+}
+
+impl Expr {
+    fn eval<S: AsRef<str>>(&self, entry: &EntryKind, folder: &[S]) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(entry, folder) && b.eval(entry, folder),
+            Expr::Or(a, b) => a.eval(entry, folder) || b.eval(entry, folder),
+            Expr::Not(a) => !a.eval(entry, folder),
+            Expr::Text(field, re) => match text(entry, *field, folder) {
+                Some(t) => re.is_match(&t),
+                None => false,
+            },
+            Expr::Created(op, date) => {
+                let created = match entry.timestamp() {
+                    Some(t) => t.naive_utc().date(),
+                    None => return false,
+                };
+                match op {
+                    DateOp::Lt => created < *date,
+                    DateOp::Le => created <= *date,
+                    DateOp::Gt => created > *date,
+                    DateOp::Ge => created >= *date,
+                    DateOp::Eq => created == *date,
+                }
+            }
+        }
+    }
+}
+
+fn text<'e, S: AsRef<str>>(
+    entry: &'e EntryKind,
+    field: TextField,
+    folder: &[S],
+) -> Option<Cow<'e, str>> {
+    match (field, entry) {
+        (TextField::Folder, _) => {
+            let names: Vec<&str> = folder.iter().map(AsRef::as_ref).collect();
+            Some(names.join("/").into())
+        }
+        (TextField::Name, EntryKind::Folder(f)) => Some(Cow::Borrowed(&f.name)),
+        (TextField::Name, EntryKind::Bookmark(b)) => Some(Cow::Borrowed(&b.name)),
+        (TextField::Name, EntryKind::Contact(c)) => Some(Cow::Borrowed(&c.name)),
+        (TextField::Url, EntryKind::Note(n)) => n.url.as_ref().map(|u| u.to_string().into()),
+        (TextField::Url, EntryKind::Bookmark(b)) => b.url.as_ref().map(|u| u.to_string().into()),
+        (TextField::Url, EntryKind::Contact(c)) => c.url.as_ref().map(|u| u.to_string().into()),
+        (TextField::Contents, EntryKind::Note(n)) => n.contents.as_deref().map(decode_line_breaks),
+        _ => None,
+    }
+}
+
+struct Parser<'q> {
+    s: &'q str,
+    pos: usize,
+}
+
+impl<'q> Parser<'q> {
+    fn or_expr(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.and_expr()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, QueryError> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+
+        self.skip_space();
+        if self.rest().starts_with('(') {
+            self.pos += 1;
+            let expr = self.or_expr()?;
+            self.skip_space();
+            if !self.rest().starts_with(')') {
+                return Err(self.error("expected \")\""));
+            }
+            self.pos += 1;
+            return Ok(expr);
+        }
+
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Expr, QueryError> {
+        let start = self.pos;
+        let name = self.take_while(|c| c.is_ascii_alphabetic());
+        let op = self.take_while(|c| ":~<>=".contains(c));
+        let value_pos = self.pos;
+        let value = self.value()?;
+
+        let field = match name {
+            "folder" => TextField::Folder,
+            "name" => TextField::Name,
+            "url" => TextField::Url,
+            "contents" => TextField::Contents,
+            "created" => {
+                let op = match op {
+                    "<" => DateOp::Lt,
+                    "<=" => DateOp::Le,
+                    ">" => DateOp::Gt,
+                    ">=" => DateOp::Ge,
+                    "=" => DateOp::Eq,
+                    _ => return Err(self.error_at(start, "created needs <, <=, >, >= or =")),
+                };
+                let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                    .map_err(|_| self.error_at(value_pos, "expected a date like 2009-01-01"))?;
+                return Ok(Expr::Created(op, date));
+            }
+            "" => return Err(self.error_at(start, "expected a condition")),
+            _ => {
+                return Err(self.error_at(
+                    start,
+                    "unknown field (folder, name, url, contents, created)",
+                ))
+            }
+        };
+
+        let re = match op {
+            ":" => glob_regex(&value),
+            "~" => value,
+            _ => return Err(self.error_at(start, &format!("{} needs : or ~", name))),
+        };
+        let re = Regex::new(&re).map_err(|e| self.error_at(value_pos, &e.to_string()))?;
+        Ok(Expr::Text(field, re))
+    }
+
+    // A quoted string, where \" and \\ are escapes, or anything up to a space or ")".
+    fn value(&mut self) -> Result<String, QueryError> {
+        if !self.rest().starts_with('"') {
+            let value = self.take_while(|c| !c.is_whitespace() && c != ')');
+            return if value.is_empty() {
+                Err(self.error("expected a value"))
+            } else {
+                Ok(value.to_string())
+            };
+        }
+
+        let start = self.pos;
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+
+        Err(self.error_at(start, "unterminated string"))
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        self.skip_space();
+        let rest = self.rest();
+        let is_keyword = rest.starts_with(word)
+            && !matches!(rest[word.len()..].chars().next(), Some(c) if c.is_ascii_alphabetic());
+        if is_keyword {
+            self.pos += word.len();
+        }
+        is_keyword
+    }
+
+    fn skip_space(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'q str {
+        let rest = &self.s[self.pos..];
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn rest(&self) -> &'q str {
+        &self.s[self.pos..]
+    }
+
+    fn error(&self, message: &str) -> QueryError {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: &str) -> QueryError {
+        QueryError {
+            pos,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn glob_regex(glob: &str) -> String {
+    let (glob, subtree) = match glob.strip_suffix("/*") {
+        Some(g) => (g, true),
+        None => (glob, false),
+    };
+
+    let parts: Vec<String> = glob.split('*').map(regex::escape).collect();
+    let tail = if subtree { "(/.*)?" } else { "" };
+    format!("^{}{}$", parts.join(".*"), tail)
+}
+
+impl<'a> Hotlist<'a> {
+    // Drops everything that doesn't match. Folders stay when they match, or for the sake of an
+    // entry in them that does.
+    pub fn select(&mut self, query: &Query) {
+        select_entries(&mut self.entries, query, &mut Vec::new());
+    }
+}
+
+fn select_entries(entries: &mut Vec<EntryKind>, query: &Query, folder: &mut Vec<String>) {
+    let all = std::mem::take(entries);
+    for mut e in all {
+        let mut keep = query.matches(&e, folder);
+        if let EntryKind::Folder(f) = &mut e {
+            folder.push(f.name.to_string());
+            select_entries(&mut f.entries, query, folder);
+            folder.pop();
+            keep = keep || !f.entries.is_empty();
+        }
+        if keep {
+            entries.push(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Query, QueryError};
+    use crate::test_support::{hotlist, ids};
+
+    // IDs of what's left, including the folders kept for what's in them.
+    fn select(query: &str) -> Vec<u32> {
+        let mut hl = hotlist();
+        hl.select(&Query::parse(query).unwrap());
+        ids(&hl.entries)
+    }
+
+    #[test]
+    fn test_folder() {
+        assert_eq!(select("folder:Work"), vec![2, 3, 5, 6]);
+        // Everything in Work and the folders in it.
+        assert_eq!(select("folder:\"Work/*\""), vec![2, 3, 4, 5, 6]);
+        assert_eq!(select("name:Old"), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_created() {
+        assert_eq!(select("created<2009-01-01"), vec![2, 3, 6, 7, 8, 9, 10]);
+        assert_eq!(select("created=2009-03-01"), vec![1, 2, 3, 4, 5]);
+    }
+
+    // Contents are matched with their line breaks decoded.
+    #[test]
+    fn test_contents() {
+        assert!(select("contents~\"^Fry\"").is_empty());
+        assert_eq!(select("contents~\"(?m)^Fry\""), vec![7]);
+    }
+
+    #[test]
+    fn test_combined() {
+        assert_eq!(
+            select("folder:\"Work/*\" and created>=2009-01-01 and url~github.com"),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            select("not (url~github or name:Old)"),
+            vec![2, 5, 6, 7, 8, 9, 10]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Query::parse("created>=2009 and").unwrap_err(),
+            QueryError {
+                pos: 9,
+                message: "expected a date like 2009-01-01".into()
+            }
+        );
+        assert!(Query::parse("colour:red").is_err());
+        assert!(Query::parse("url~foo bar").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+// A summary of what's in a hotlist, for keeping an eye on how an archive changes over time.
+// Folder paths are written like "/Work/Old", with "/" for the top level.
+
+use crate::ast::{folder_path, walk, EntryKind, Folder, Hotlist, HotlistUrl};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use chrono::Datelike;
+use serde_json::{json, Value};
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Stats {
+    pub notes: usize,
+    pub folders: usize,
+    pub bookmarks: usize,
+    pub contacts: usize,
+    // Notes directly in each folder, including folders without any.
+    pub notes_per_folder: BTreeMap<String, usize>,
+    // How many folders deep the deepest folder is; 0 if there are none.
+    pub deepest_nesting: usize,
+    // Notes by the year they were created.
+    pub notes_per_year: BTreeMap<i32, usize>,
+    // Hosts of the URLs of notes, bookmarks and contacts, most used first.
+    pub url_hosts: Vec<(String, usize)>,
+    pub notes_without_url: usize,
+    pub notes_in_trash: usize,
+    pub empty_folders: Vec<String>,
+}
+
+impl Stats {
+    pub fn new(hl: &Hotlist) -> Self {
+        let mut stats = Stats::default();
+        let mut hosts = HashMap::new();
+        stats.notes_per_folder.insert(folder_path::<&str>(&[]), 0);
+        walk(&hl.entries, |folders, e| {
+            stats.count(folders, e, &mut hosts)
+        });
+
+        stats.url_hosts = hosts.into_iter().collect();
+        stats
+            .url_hosts
+            .sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        stats
+    }
+
+    fn count(&mut self, folders: &[&Folder], e: &EntryKind, hosts: &mut HashMap<String, usize>) {
+        let mut folder: Vec<&str> = folders.iter().map(|f| &*f.name).collect();
+        let url = match e {
+            EntryKind::Folder(f) => {
+                self.folders += 1;
+                folder.push(&f.name);
+                self.deepest_nesting = self.deepest_nesting.max(folder.len());
+                self.notes_per_folder
+                    .entry(folder_path(&folder))
+                    .or_insert(0);
+                if f.entries.is_empty() {
+                    self.empty_folders.push(folder_path(&folder));
+                }
+                return;
+            }
+            EntryKind::Note(n) => {
+                self.notes += 1;
+                *self
+                    .notes_per_folder
+                    .entry(folder_path(&folder))
+                    .or_default() += 1;
+                *self.notes_per_year.entry(n.timestamp.year()).or_default() += 1;
+                if n.url.is_none() {
+                    self.notes_without_url += 1;
+                }
+                if folders.iter().any(|f| f.trash) {
+                    self.notes_in_trash += 1;
+                }
+                &n.url
+            }
+            EntryKind::Bookmark(b) => {
+                self.bookmarks += 1;
+                &b.url
+            }
+            EntryKind::Contact(c) => {
+                self.contacts += 1;
+                &c.url
+            }
+            EntryKind::Separator(_) => return,
+        };
+
+        if let Some(HotlistUrl::Valid(u)) = url {
+            if let Some(host) = u.host_str() {
+                *hosts.entry(host.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    // Only the first top_hosts URL hosts are included.
+    pub fn to_json(&self, top_hosts: usize) -> Value {
+        let hosts: Vec<Value> = self
+            .url_hosts
+            .iter()
+            .take(top_hosts)
+            .map(|(host, count)| json!({ "host": host, "count": count }))
+            .collect();
+        let years: BTreeMap<String, usize> = self
+            .notes_per_year
+            .iter()
+            .map(|(year, count)| (year.to_string(), *count))
+            .collect();
+
+        json!({
+            "notes": self.notes,
+            "folders": self.folders,
+            "bookmarks": self.bookmarks,
+            "contacts": self.contacts,
+            "notes_per_folder": self.notes_per_folder,
+            "deepest_nesting": self.deepest_nesting,
+            "notes_per_year": years,
+            "url_hosts": hosts,
+            "notes_without_url": self.notes_without_url,
+            "notes_in_trash": self.notes_in_trash,
+            "empty_folders": self.empty_folders,
+        })
+    }
+
+    // The report as text, with only the first top_hosts URL hosts.
+    pub fn display(&self, top_hosts: usize) -> impl fmt::Display + '_ {
+        StatsDisplay(self, top_hosts)
+    }
+}
+
+struct StatsDisplay<'s>(&'s Stats, usize);
+
+impl<'s> fmt::Display for StatsDisplay<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let StatsDisplay(s, top_hosts) = self;
+
+        writeln!(f, "Notes: {}", s.notes)?;
+        writeln!(f, "Folders: {}", s.folders)?;
+        writeln!(f, "Bookmarks: {}", s.bookmarks)?;
+        writeln!(f, "Contacts: {}", s.contacts)?;
+        writeln!(f, "Notes without URL: {}", s.notes_without_url)?;
+        writeln!(f, "Notes in trash: {}", s.notes_in_trash)?;
+        writeln!(f, "Deepest nesting: {}", s.deepest_nesting)?;
+
+        let width = s
+            .notes_per_folder
+            .keys()
+            .map(String::len)
+            .max()
+            .unwrap_or(0);
+        writeln!(f, "\nNotes per folder:")?;
+        for (folder, count) in &s.notes_per_folder {
+            writeln!(f, "  {:w$}  {}", folder, count, w = width)?;
+        }
+
+        writeln!(f, "\nNotes per year:")?;
+        for (year, count) in &s.notes_per_year {
+            writeln!(f, "  {}  {}", year, count)?;
+        }
+
+        let hosts = &s.url_hosts[..s.url_hosts.len().min(*top_hosts)];
+        let width = hosts.iter().map(|(h, _)| h.len()).max().unwrap_or(0);
+        writeln!(f, "\nTop URL hosts:")?;
+        for (host, count) in hosts {
+            writeln!(f, "  {:w$}  {}", host, count, w = width)?;
+        }
+
+        writeln!(f, "\nEmpty folders:")?;
+        for folder in &s.empty_folders {
+            writeln!(f, "  {}", folder)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+    use crate::test_support::hotlist;
+
+    #[test]
+    fn test_counts() {
+        let stats = Stats::new(&hotlist());
+        assert_eq!((stats.notes, stats.folders, stats.bookmarks), (5, 4, 1));
+        assert_eq!(stats.contacts, 0);
+        assert_eq!(stats.notes_without_url, 1);
+        assert_eq!(stats.notes_in_trash, 1);
+    }
+
+    #[test]
+    fn test_folders() {
+        let stats = Stats::new(&hotlist());
+        assert_eq!(
+            stats.notes_per_folder.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("/".into(), 2),
+                ("/Trash".into(), 1),
+                ("/Work".into(), 1),
+                ("/Work/Empty".into(), 0),
+                ("/Work/Old".into(), 1),
+            ]
+        );
+        assert_eq!(stats.deepest_nesting, 2);
+        assert_eq!(stats.empty_folders, vec!["/Work/Empty"]);
+    }
+
+    #[test]
+    fn test_years_and_hosts() {
+        let stats = Stats::new(&hotlist());
+        assert_eq!(
+            stats.notes_per_year.into_iter().collect::<Vec<_>>(),
+            vec![(2001, 2), (2009, 3)]
+        );
+        assert_eq!(
+            stats.url_hosts,
+            vec![("example.com".into(), 3), ("github.com".into(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let json = Stats::new(&hotlist()).to_json(1);
+        assert_eq!(json["url_hosts"].as_array().unwrap().len(), 1);
+        assert_eq!(json["notes_per_year"]["2009"], 3);
+    }
+}
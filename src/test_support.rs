@@ -0,0 +1,125 @@
+// A sample hotlist and helpers shared by the unit tests. Tests that need something else start from
+// HOTLIST and edit it, either as text or through the edit API.
+
+use crate::ast::{walk, EntryKind, Hotlist};
+use crate::parser::{parse_str, ParseOptions};
+
+use uuid::Uuid;
+
+// Every entry's UUID is its ID, and everything was created in 2001 or 2009, so none of the check
+// rules fire. "Top." and the trash folder are at the top level.
+//
+// /Top.
+// /Work/Old/Code review.
+// /Work/Ticket.
+// /Work/Empty/
+// /Pancakes
+// /Example (a bookmark)
+// /Trash/Deleted.
+pub const HOTLIST: &str = "Opera Hotlist version 2.0\n\
+Options: encoding = utf8, version=3\n\
+\n\
+#NOTE\n\
+\tID=1\n\
+\tUNIQUEID=00000000000000000000000000000001\n\
+\tNAME=Top.\n\
+\tURL=https://github.com/\n\
+\tCREATED=1235865600\n\
+\n\
+#FOLDER\n\
+\tID=2\n\
+\tNAME=Work\n\
+\tCREATED=1000000000\n\
+\tUNIQUEID=00000000000000000000000000000002\n\
+\n\
+#FOLDER\n\
+\tID=3\n\
+\tNAME=Old\n\
+\tCREATED=1000000000\n\
+\tUNIQUEID=00000000000000000000000000000003\n\
+\n\
+#NOTE\n\
+\tID=4\n\
+\tUNIQUEID=00000000000000000000000000000004\n\
+\tNAME=Code review.\n\
+\tURL=https://github.com/a/b\n\
+\tCREATED=1235865600\n\
+\n\
+-\n\
+\n\
+#NOTE\n\
+\tID=5\n\
+\tUNIQUEID=00000000000000000000000000000005\n\
+\tNAME=Ticket.\n\
+\tURL=https://example.com/\n\
+\tCREATED=1235865600\n\
+\n\
+#FOLDER\n\
+\tID=6\n\
+\tNAME=Empty\n\
+\tCREATED=1000000000\n\
+\tUNIQUEID=00000000000000000000000000000006\n\
+\n\
+-\n\
+\n\
+-\n\
+\n\
+#NOTE\n\
+\tID=7\n\
+\tUNIQUEID=00000000000000000000000000000007\n\
+\tNAME=Pancakes\x02\x02Two cups of flour, one egg and milk.\x02\x02Fry.\n\
+\tURL=https://example.com/pancakes\n\
+\tCREATED=1000000000\n\
+\n\
+#URL\n\
+\tID=8\n\
+\tNAME=Example\n\
+\tURL=http://example.com/\n\
+\tCREATED=1000000000\n\
+\tUNIQUEID=00000000000000000000000000000008\n\
+\n\
+#FOLDER\n\
+\tID=9\n\
+\tNAME=Trash\n\
+\tCREATED=1000000000\n\
+\tTRASH FOLDER=YES\n\
+\tUNIQUEID=00000000000000000000000000000009\n\
+\n\
+#NOTE\n\
+\tID=10\n\
+\tUNIQUEID=0000000000000000000000000000000A\n\
+\tNAME=Deleted.\n\
+\tCREATED=1000000000\n\
+\n\
+-\n";
+
+pub fn parse(text: &str) -> Hotlist<'_> {
+    parse_str(text, &ParseOptions::default()).unwrap().hotlist
+}
+
+pub fn hotlist() -> Hotlist<'static> {
+    parse(HOTLIST)
+}
+
+// The UUID of the entry with this ID in HOTLIST.
+pub fn uuid(id: u32) -> Uuid {
+    Uuid::from_u128(id.into())
+}
+
+// IDs in tree order.
+pub fn ids(entries: &[EntryKind]) -> Vec<u32> {
+    let mut ids = Vec::new();
+    walk(entries, |_, e| ids.extend(e.id()));
+    ids
+}
+
+// ID and contents of the notes directly in entries.
+pub fn notes<'e>(entries: &'e [EntryKind]) -> Vec<(u32, &'e str)> {
+    entries
+        .iter()
+        .filter_map(|e| match e {
+            EntryKind::Note(n) => Some((n.id, n.contents.as_deref().unwrap_or(""))),
+            _ => None,
+        })
+        .collect()
+}
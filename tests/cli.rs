@@ -0,0 +1,117 @@
+use std::fs;
+use std::process::Command;
+
+const INPUT: &str = "Opera Hotlist version 2.0\n\
+Options: encoding = utf8, version=3\n\
+\n\
+#URL\n\
+\tID=1\n\
+\tUNIQUEID=00000000000000000000000000000001\n\
+\tNAME=Broken\n\
+\tURL=not a url\n\
+\tCREATED=0\n";
+
+// Diagnostics in JSON mode are for tools, but so is the converted output; they mustn't mix.
+#[test]
+fn test_json_warnings_keep_stdout_clean() {
+    let path = std::env::temp_dir().join(format!("hl2html-cli-{}.adr", std::process::id()));
+    fs::write(&path, INPUT).unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_hl2html"))
+        .args(["convert", "--lenient", "--message-format", "json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("Broken"));
+    assert!(!stdout.contains("\"level\""));
+
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    let warnings: Vec<serde_json::Value> = stderr
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["level"], "warning");
+    assert_eq!(warnings[0]["kind"], "InvalidUrl");
+}
+
+// The test harness captures stdout, so it isn't a terminal.
+#[test]
+fn test_search_color() {
+    let path = std::env::temp_dir().join(format!("hl2html-color-{}.adr", std::process::id()));
+    fs::write(&path, INPUT).unwrap();
+
+    let search = |args: &[&str]| {
+        let out = Command::new(env!("CARGO_BIN_EXE_hl2html"))
+            .args(["search", "--lenient"])
+            .args(args)
+            .arg(&path)
+            .arg("a url")
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let plain = search(&[]);
+    let always = search(&["--color", "always"]);
+    fs::remove_file(&path).unwrap();
+
+    assert!(plain.contains("not a url"));
+    assert!(!plain.contains('\x1b'));
+    assert!(always.contains("not \x1b[1;31ma url\x1b[0m"));
+}
+
+// --format is what check reports, --message-format how the parse warnings are shown.
+#[test]
+fn test_check_report_and_message_formats() {
+    let path = std::env::temp_dir().join(format!("hl2html-check-{}.adr", std::process::id()));
+    fs::write(&path, INPUT).unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_hl2html"))
+        .args([
+            "check",
+            "--lenient",
+            "--format",
+            "json",
+            "--message-format",
+            "human",
+        ])
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let findings: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(findings.len(), 1);
+
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.starts_with("warning: "));
+}
+
+#[test]
+fn test_merge_needs_two_files() {
+    let path = std::env::temp_dir().join(format!("hl2html-merge-{}.adr", std::process::id()));
+    fs::write(&path, INPUT).unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_hl2html"))
+        .args(["merge", "--lenient"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(out.status.code(), Some(1));
+    assert!(out.stdout.is_empty());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("at least two input files are required"));
+}